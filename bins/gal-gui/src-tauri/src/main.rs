@@ -10,7 +10,7 @@ use gal_runtime::{
     *,
 };
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fmt::Display};
+use std::{collections::HashMap, fmt::Display, path::Path, sync::Arc};
 use tauri::{async_runtime::Mutex, command, AppHandle, Manager, State};
 
 type CommandResult<T> = std::result::Result<T, CommandError>;
@@ -79,11 +79,18 @@ async fn open_game(handle: AppHandle, storage: State<'_, Storage>) -> CommandRes
             }
         }
         let mut ctx = context.await?;
+        ctx.set_storage(storage.saves.clone());
+        let event_handle = handle.clone();
+        ctx.subscribe(move |event| {
+            if let Err(e) = event_handle.emit_all("gal://context_event", event) {
+                warn!("Emit context event failed: {}", e);
+            }
+        });
         let window = handle.get_window("main").unwrap();
         window.set_title(&ctx.game.title)?;
         let settings = {
             emit_open_status(&handle, OpenGameStatus::LoadSettings)?;
-            load_settings(&storage.ident).await.unwrap_or_else(|e| {
+            load_settings(&*storage.saves).await.unwrap_or_else(|e| {
                 warn!("Load settings failed: {}", e);
                 Settings::new()
             })
@@ -91,7 +98,7 @@ async fn open_game(handle: AppHandle, storage: State<'_, Storage>) -> CommandRes
         ctx.set_settings(settings);
         emit_open_status(&handle, OpenGameStatus::LoadGlobalRecords)?;
         ctx.set_global_record(
-            load_global_record(&storage.ident, &ctx.game.title)
+            load_global_record(&*storage.saves, &ctx.game.title)
                 .await
                 .unwrap_or_else(|e| {
                     warn!("Load global records failed: {}", e);
@@ -99,7 +106,7 @@ async fn open_game(handle: AppHandle, storage: State<'_, Storage>) -> CommandRes
                 }),
         );
         emit_open_status(&handle, OpenGameStatus::LoadRecords)?;
-        *storage.records.lock().await = load_records(&storage.ident, &ctx.game.title)
+        *storage.records.lock().await = load_records(&*storage.saves, &ctx.game.title)
             .await
             .unwrap_or_else(|e| {
                 warn!("Load records failed: {}", e);
@@ -143,7 +150,7 @@ async fn save_record_to(index: usize, storage: State<'_, Storage>) -> CommandRes
         .lock()
         .await
         .as_ref()
-        .map(|ctx| ctx.record.clone())
+        .map(|ctx| ctx.compacted_record())
     {
         if index >= records.len() {
             records.push(record);
@@ -158,23 +165,58 @@ async fn save_record_to(index: usize, storage: State<'_, Storage>) -> CommandRes
 async fn save_all(storage: State<'_, Storage>) -> CommandResult<()> {
     if let Some(context) = storage.context.lock().await.as_ref() {
         let game = &context.game.title;
-        save_settings(&storage.ident, context.settings()).await?;
-        save_global_record(&storage.ident, game, context.global_record()).await?;
-        save_records(&storage.ident, game, &storage.records.lock().await).await?;
+        save_settings(&*storage.saves, context.settings()).await?;
+        save_global_record(
+            &*storage.saves,
+            game,
+            context.global_record(),
+            SaveFormat::Json,
+        )
+        .await?;
+        save_records(
+            &*storage.saves,
+            game,
+            &storage.records.lock().await,
+            SaveFormat::Binary,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+#[command]
+async fn quick_save(storage: State<'_, Storage>) -> CommandResult<()> {
+    if let Some(context) = storage.context.lock().await.as_ref() {
+        context.save_slot(SaveSlot::Quick).await?;
+    }
+    Ok(())
+}
+
+#[command]
+async fn quick_load(storage: State<'_, Storage>) -> CommandResult<()> {
+    if let Some(ctx) = storage.context.lock().await.as_mut() {
+        for unresolved in ctx.load_slot(SaveSlot::Quick).await? {
+            warn!(
+                "Cannot resolve history {}: para {}, act {}",
+                unresolved.index, unresolved.ctx.cur_para, unresolved.ctx.cur_act
+            );
+        }
+        *storage.action.lock().await = ctx.record.history.last().cloned();
+    } else {
+        warn!("Game hasn't been loaded.")
     }
     Ok(())
 }
 
 #[command]
 fn choose_locale(locales: Vec<Locale>) -> CommandResult<Option<Locale>> {
-    let current = Locale::current();
+    let current = LocalePreferences::current();
     debug!("Choose {} from {:?}", current, locales);
-    Ok(current.choose_from(&locales).cloned())
+    Ok(current.choose_from(&locales).map(|(loc, _)| loc.clone()))
 }
 
-#[derive(Default)]
 struct Storage {
-    ident: String,
+    saves: Arc<dyn gal_runtime::Storage>,
     config: String,
     records: Mutex<Vec<ActionRecord>>,
     context: Mutex<Option<Context>>,
@@ -182,11 +224,13 @@ struct Storage {
 }
 
 impl Storage {
-    pub fn new(ident: impl Into<String>, config: impl Into<String>) -> Self {
+    pub fn new(saves: Arc<dyn gal_runtime::Storage>, config: impl Into<String>) -> Self {
         Self {
-            ident: ident.into(),
+            saves,
             config: config.into(),
-            ..Default::default()
+            records: Default::default(),
+            context: Default::default(),
+            action: Default::default(),
         }
     }
 }
@@ -195,7 +239,7 @@ impl Storage {
 struct GameInfo {
     pub title: String,
     pub author: String,
-    pub props: HashMap<String, String>,
+    pub props: HashMap<String, PropValue>,
 }
 
 impl GameInfo {
@@ -219,9 +263,34 @@ async fn info(storage: State<'_, Storage>) -> CommandResult<Option<GameInfo>> {
     }
 }
 
+fn prefer_locale(ctx: &mut Context, locale: Locale) {
+    let mut prefs = ctx.settings().lang.clone();
+    prefs.prefer(locale);
+    ctx.set_locale_preferences(prefs);
+}
+
+#[command]
+async fn switch_locale(
+    locale: Locale,
+    backlog: bool,
+    storage: State<'_, Storage>,
+) -> CommandResult<()> {
+    if let Some(ctx) = storage.context.lock().await.as_mut() {
+        prefer_locale(ctx, locale.clone());
+        ctx.switch_locale(ctx.settings().lang.clone(), backlog);
+        let mut action = storage.action.lock().await;
+        if let Some(act) = action.as_ref().and_then(|act| ctx.rerender(act)) {
+            *action = Some(act);
+        }
+        info!("Switch locale to {}.", locale);
+    }
+    Ok(())
+}
+
 #[command]
 async fn start_new(locale: Locale, storage: State<'_, Storage>) -> CommandResult<()> {
     if let Some(ctx) = storage.context.lock().await.as_mut() {
+        prefer_locale(ctx, locale.clone());
         ctx.init_new();
         info!("Init new context with locale {}.", locale);
     } else {
@@ -237,10 +306,15 @@ async fn start_record(
     storage: State<'_, Storage>,
 ) -> CommandResult<()> {
     if let Some(ctx) = storage.context.lock().await.as_mut() {
+        prefer_locale(ctx, locale.clone());
         let raw_ctx = storage.records.lock().await[index].clone();
-        let last_line = raw_ctx.history.last().unwrap();
-        *storage.action.lock().await = Some(last_line.clone());
-        ctx.init_context(raw_ctx);
+        for unresolved in ctx.init_context(raw_ctx) {
+            warn!(
+                "Cannot resolve history {}: para {}, act {}",
+                unresolved.index, unresolved.ctx.cur_para, unresolved.ctx.cur_act
+            );
+        }
+        *storage.action.lock().await = ctx.record.history.last().cloned();
         info!("Init new context with locale {}.", locale);
     } else {
         warn!("Game hasn't been loaded.")
@@ -252,6 +326,11 @@ async fn start_record(
 async fn next_run(storage: State<'_, Storage>) -> CommandResult<bool> {
     let mut context = storage.context.lock().await;
     let action = context.as_mut().and_then(|context| context.next_run());
+    if let Some(context) = context.as_mut() {
+        if let Err(e) = context.auto_save().await {
+            warn!("Auto save failed: {}", e);
+        }
+    }
     if let Some(action) = action {
         debug!("Next action: {:?}", action);
         *storage.action.lock().await = Some(action);
@@ -277,21 +356,6 @@ async fn next_back_run(storage: State<'_, Storage>) -> CommandResult<bool> {
     }
 }
 
-#[command]
-async fn current_visited(storage: State<'_, Storage>) -> CommandResult<bool> {
-    let action = storage.action.lock().await;
-    let visited = if let Some(action) = action.as_ref() {
-        let context = storage.context.lock().await;
-        context
-            .as_ref()
-            .map(|context| context.visited(action))
-            .unwrap_or_default()
-    } else {
-        false
-    };
-    Ok(visited)
-}
-
 #[command]
 async fn current_run(storage: State<'_, Storage>) -> CommandResult<Option<Action>> {
     Ok(storage.action.lock().await.as_ref().cloned())
@@ -326,6 +390,39 @@ async fn history(storage: State<'_, Storage>) -> CommandResult<Vec<Action>> {
     Ok(hs)
 }
 
+#[command]
+async fn unlocks(storage: State<'_, Storage>) -> CommandResult<Vec<UnlockInfo>> {
+    Ok(storage
+        .context
+        .lock()
+        .await
+        .as_ref()
+        .map(|context| context.unlocks())
+        .unwrap_or_default())
+}
+
+#[command]
+async fn endings(storage: State<'_, Storage>) -> CommandResult<Vec<EndingInfo>> {
+    Ok(storage
+        .context
+        .lock()
+        .await
+        .as_ref()
+        .map(|context| context.endings())
+        .unwrap_or_default())
+}
+
+#[command]
+async fn completion(storage: State<'_, Storage>) -> CommandResult<f64> {
+    Ok(storage
+        .context
+        .lock()
+        .await
+        .as_ref()
+        .map(|context| context.completion())
+        .unwrap_or_default())
+}
+
 fn main() -> Result<()> {
     let port =
         portpicker::pick_unused_port().ok_or_else(|| anyhow!("failed to find unused port"))?;
@@ -362,7 +459,17 @@ fn main() -> Result<()> {
                 .as_str()
                 .map(|s| s.to_string())
                 .unwrap_or_default();
-            app.manage(Storage::new(ident, config));
+            let portable = matches.args["portable"].value.as_bool().unwrap_or_default();
+            let saves: Arc<dyn gal_runtime::Storage> = if portable {
+                let root = Path::new(&config)
+                    .parent()
+                    .unwrap_or_else(|| Path::new("."))
+                    .join("data");
+                Arc::new(DirStorage::new(root))
+            } else {
+                Arc::new(OsStorage::new(ident))
+            };
+            app.manage(Storage::new(saves, config));
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -372,16 +479,21 @@ fn main() -> Result<()> {
             get_records,
             save_record_to,
             save_all,
+            quick_save,
+            quick_load,
             choose_locale,
             info,
             start_new,
+            switch_locale,
             start_record,
             next_run,
             next_back_run,
             current_run,
-            current_visited,
             switch,
             history,
+            unlocks,
+            endings,
+            completion,
         ])
         .run(tauri::generate_context!())?;
     Ok(())
@@ -23,8 +23,39 @@ export enum OpenGameStatusType {
     Loaded,
 }
 
+export interface ContextEvent {
+    t: keyof typeof ContextEventType,
+    data?: object,
+}
+
+export enum ContextEventType {
+    ParagraphChanged,
+    VariableChanged,
+    Action,
+    SwitchPresented,
+    GameEnded,
+}
+
+export interface ActionEvent {
+    action: Action,
+    visited: boolean,
+}
+
 export interface Settings {
-    lang: Locale,
+    lang: Locale[],
+    history?: HistorySettings,
+    auto_save?: AutoSaveSettings,
+}
+
+export interface HistorySettings {
+    cap?: number,
+    snapshot_interval: number,
+}
+
+export interface AutoSaveSettings {
+    on_switch: boolean,
+    on_paragraph: boolean,
+    interval: number,
 }
 
 export interface RawContext {
@@ -35,6 +66,19 @@ export interface RawContext {
     bgm?: string,
 }
 
+export type PropValue = null | boolean | number | string | PropValue[] | { [key: string]: PropValue }
+
+// The old string properties are comma-joined lists.
+export function prop_list(value?: PropValue): string[] {
+    if (Array.isArray(value)) {
+        return value.map(v => String(v))
+    }
+    if (value === null || value === undefined) {
+        return []
+    }
+    return String(value).split(",").filter(s => s.length != 0)
+}
+
 export interface GameInfo {
     title: string,
     author: string,
@@ -55,7 +99,7 @@ export interface Action {
         efm?: string,
         voice?: string,
         video?: string,
-        ch_models_count?: string,
+        ch_models?: PropValue,
     },
 }
 
@@ -94,16 +138,22 @@ export function save_record_to(index: number): Promise<void> {
     return invoke("save_record_to", { index: index })
 }
 
-export async function set_locale(loc: Locale): Promise<void> {
-    let settings = await get_settings() ?? { lang: "" };
-    settings.lang = loc
-    await set_settings(settings)
+export function set_locale(loc: Locale): Promise<void> {
+    return invoke("switch_locale", { locale: loc, backlog: true })
 }
 
 export function save_all(): Promise<void> {
     return invoke("save_all")
 }
 
+export function quick_save(): Promise<void> {
+    return invoke("quick_save")
+}
+
+export function quick_load(): Promise<void> {
+    return invoke("quick_load")
+}
+
 export function choose_locale(locales: Locale[]): Promise<Locale | undefined> {
     return invoke("choose_locale", { locales: locales })
 }
@@ -137,10 +187,6 @@ export function current_run(): Promise<Action | undefined> {
     return invoke("current_run")
 }
 
-export async function current_visited(): Promise<boolean> {
-    return invoke("current_visited")
-}
-
 export function switch_(i: number): Promise<void> {
     return invoke("switch", { i: i })
 }
@@ -149,6 +195,33 @@ export function history(): Promise<Action[]> {
     return invoke("history")
 }
 
+export interface UnlockInfo {
+    id: string,
+    kind: "achievement" | "cg" | "music",
+    title: string,
+    description: string,
+    path?: string,
+    unlocked?: number,
+}
+
+export function unlocks(): Promise<UnlockInfo[]> {
+    return invoke("unlocks")
+}
+
+export interface EndingInfo {
+    tag: string,
+    title?: string,
+    reached?: number,
+}
+
+export function endings(): Promise<EndingInfo[]> {
+    return invoke("endings")
+}
+
+export function completion(): Promise<number> {
+    return invoke("completion")
+}
+
 export function merge_lines(lines: ActionLine[]): string {
     let res = ""
     lines.forEach(s => {
@@ -1,49 +1,198 @@
-use clap::Parser;
+use clap::{ArgEnum, Parser, Subcommand};
 use gal_runtime::{
     anyhow::{bail, Result},
-    log::LevelFilter,
+    log::{warn, LevelFilter},
     *,
 };
 use std::{
     ffi::OsString,
     io::{stdin, stdout, Write},
+    path::Path,
+    sync::Arc,
 };
 
 #[derive(Debug, Parser)]
 #[clap(about, version, author)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct Options {
-    input: OsString,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+    #[clap(required = true)]
+    input: Option<OsString>,
     #[clap(long)]
     check: bool,
     #[clap(long)]
     auto: bool,
     #[clap(short, long)]
+    locale: Option<LocalePreferences>,
+    #[clap(long, value_name = "OUTPUT")]
+    convert: Option<OsString>,
+    /// Store the settings and saves in the "data" folder beside the config.
+    #[clap(long)]
+    portable: bool,
+    /// Continue from the auto save.
+    #[clap(long)]
+    resume: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum Commands {
+    /// Export the paragraph graph.
+    Graph(GraphOptions),
+    /// Report the translation completeness and parity.
+    I18nReport(I18nReportOptions),
+    /// Export the texts to translate to a PO catalog.
+    ExportPo(ExportPoOptions),
+    /// Import a translated PO catalog to an included file.
+    ImportPo(ImportPoOptions),
+}
+
+#[derive(Debug, Parser)]
+struct GraphOptions {
+    input: OsString,
+    #[clap(short, long, arg_enum, default_value = "dot")]
+    format: GraphFormat,
+    #[clap(short, long)]
+    output: Option<OsString>,
+    #[clap(short, long)]
     locale: Option<Locale>,
 }
 
-fn read_line() -> Result<String> {
+#[derive(Debug, Clone, ArgEnum)]
+enum GraphFormat {
+    Dot,
+    Json,
+}
+
+#[derive(Debug, Parser)]
+struct I18nReportOptions {
+    input: OsString,
+    #[clap(short, long, arg_enum, default_value = "text")]
+    format: ReportFormat,
+    #[clap(short, long)]
+    locale: Option<Locale>,
+}
+
+#[derive(Debug, Clone, ArgEnum)]
+enum ReportFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Parser)]
+struct ExportPoOptions {
+    input: OsString,
+    #[clap(short, long)]
+    locale: Locale,
+    #[clap(short, long)]
+    output: Option<OsString>,
+}
+
+#[derive(Debug, Parser)]
+struct ImportPoOptions {
+    input: OsString,
+    catalog: OsString,
+    #[clap(short, long)]
+    locale: Option<Locale>,
+    #[clap(short, long)]
+    output: OsString,
+}
+
+/// Returns [`None`] at the end of the input.
+fn read_line() -> Result<Option<String>> {
     stdout().flush()?;
     let mut s = String::new();
-    stdin().read_line(&mut s)?;
-    Ok(s)
+    if stdin().read_line(&mut s)? == 0 {
+        Ok(None)
+    } else {
+        Ok(Some(s))
+    }
 }
 
-fn pause(auto: bool) -> Result<()> {
+/// Returns `false` at the end of the input.
+fn pause(auto: bool) -> Result<bool> {
     if auto {
         println!();
+        Ok(true)
     } else {
-        read_line()?;
+        Ok(read_line()?.is_some())
+    }
+}
+
+async fn graph(opts: GraphOptions) -> Result<()> {
+    let game = Game::open(&opts.input).await?;
+    let graphs = match &opts.locale {
+        Some(loc) => vec![StoryGraph::new(&game, loc)],
+        None => game.story_graphs(),
+    };
+    let s = match opts.format {
+        GraphFormat::Dot => graphs.iter().map(|g| g.to_dot()).collect(),
+        GraphFormat::Json => serde_json::to_string_pretty(&graphs)? + "\n",
+    };
+    match &opts.output {
+        Some(output) => tokio::fs::write(output, s).await?,
+        None => print!("{}", s),
+    }
+    Ok(())
+}
+
+async fn i18n_report(opts: I18nReportOptions) -> Result<()> {
+    let game = Game::open(&opts.input).await?;
+    let mut report = game.i18n_report();
+    if let Some(loc) = &opts.locale {
+        report.locales.retain(|r| &r.locale == loc);
+    }
+    match opts.format {
+        ReportFormat::Text => print!("{}", report),
+        ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+    }
+    Ok(())
+}
+
+async fn export_po(opts: ExportPoOptions) -> Result<()> {
+    let game = Game::open(&opts.input).await?;
+    let s = game.export_po(&opts.locale).to_string();
+    match &opts.output {
+        Some(output) => tokio::fs::write(output, s).await?,
+        None => print!("{}", s),
     }
     Ok(())
 }
 
+async fn import_po(opts: ImportPoOptions) -> Result<()> {
+    let mut game = Game::open(&opts.input).await?;
+    let catalog = PoCatalog::parse(&tokio::fs::read_to_string(&opts.catalog).await?)?;
+    let loc = match opts.locale.or_else(|| catalog.locale.clone()) {
+        Some(loc) => loc,
+        None => bail!("The locale is not specified."),
+    };
+    game.import_po(&loc, &catalog)?;
+    game.take_locale(&loc).write(&opts.output).await?;
+    Ok(())
+}
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
     let opts = Options::parse();
     env_logger::Builder::from_default_env()
         .filter_module("wasmer", LevelFilter::Warn)
         .try_init()?;
-    let context = Context::open(&opts.input, FrontendType::Text);
+    match opts.command {
+        Some(Commands::Graph(opts)) => return graph(opts).await,
+        Some(Commands::I18nReport(opts)) => return i18n_report(opts).await,
+        Some(Commands::ExportPo(opts)) => return export_po(opts).await,
+        Some(Commands::ImportPo(opts)) => return import_po(opts).await,
+        None => {}
+    }
+    let input = opts.input.unwrap_or_default();
+    if let Some(output) = &opts.convert {
+        let mut game = Game::open(&input).await?;
+        // The included files have been merged.
+        game.include.clear();
+        game.write(output).await?;
+        return Ok(());
+    }
+    let context = Context::open(&input, FrontendType::Text);
     pin_mut!(context);
     while let Some(status) = context.next().await {
         match status {
@@ -58,11 +207,61 @@ async fn main() -> Result<()> {
     if opts.check && !ctx.check() {
         bail!("Check failed.");
     }
-    ctx.init_new();
-    if let Some(loc) = opts.locale {
-        ctx.set_locale(loc);
+    let storage: Arc<dyn Storage> = if opts.portable {
+        let root = Path::new(&input)
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join("data");
+        Arc::new(DirStorage::new(root))
+    } else {
+        Arc::new(OsStorage::new("com.berrysoft.gal"))
+    };
+    ctx.set_settings(load_settings(&*storage).await.unwrap_or_else(|e| {
+        warn!("Load settings failed: {}", e);
+        Settings::new()
+    }));
+    ctx.set_global_record(
+        load_global_record(&*storage, &ctx.game.title)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Load global records failed: {}", e);
+                Default::default()
+            }),
+    );
+    ctx.set_storage(storage.clone());
+    if opts.resume {
+        for unresolved in ctx.load_slot(SaveSlot::Auto).await? {
+            warn!(
+                "Cannot resolve history {}: para {}, act {}",
+                unresolved.index, unresolved.ctx.cur_para, unresolved.ctx.cur_act
+            );
+        }
+    } else {
+        ctx.init_new();
+    }
+    if let Some(prefs) = opts.locale {
+        ctx.set_locale_preferences(prefs);
+    }
+    // The game ends, or quits at the end of the input.
+    let res = run(&mut ctx, opts.auto).await;
+    if let Err(e) = save_global_record(
+        &*storage,
+        &ctx.game.title,
+        ctx.global_record(),
+        SaveFormat::Json,
+    )
+    .await
+    {
+        warn!("Save global records failed: {}", e);
     }
+    res
+}
+
+async fn run(ctx: &mut Context, auto: bool) -> Result<()> {
     while let Some(action) = ctx.next_run() {
+        if let Err(e) = ctx.auto_save().await {
+            warn!("Auto save failed: {}", e);
+        }
         if let Some(name) = &action.character {
             print!("_{}_", name);
         }
@@ -79,7 +278,10 @@ async fn main() -> Result<()> {
             }
             println!();
             loop {
-                let s = read_line()?;
+                let s = match read_line()? {
+                    Some(s) => s,
+                    None => return Ok(()),
+                };
                 if let Ok(i) = s.trim().parse::<usize>() {
                     let valid =
                         i > 0 && i <= action.switch_actions.len() && action.switches[i - 1].enabled;
@@ -90,8 +292,8 @@ async fn main() -> Result<()> {
                 }
                 println!("Invalid switch, enter again!");
             }
-        } else {
-            pause(opts.auto)?;
+        } else if !pause(auto)? {
+            break;
         }
     }
     Ok(())
@@ -4,6 +4,13 @@ use gal_bindings::*;
 fn plugin_type() -> PluginType {
     PluginType::builder()
         .text(["par", "textrm", "textsf", "texttt", "ruby"])
+        .text_args([
+            ("par", 0),
+            ("textrm", 1),
+            ("textsf", 1),
+            ("texttt", 1),
+            ("ruby", 2),
+        ])
         .build()
 }
 
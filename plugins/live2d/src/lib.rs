@@ -19,13 +19,14 @@ const CH_ALL: &str = "__ch_all__";
 fn find_model(
     ch: &str,
     root_path: impl AsRef<Path>,
-    game_props: &HashMap<String, String>,
+    game_props: &HashMap<String, PropValue>,
 ) -> Option<PathBuf> {
     let base_dir = root_path.as_ref().join(
         game_props
             .get("ch_models")
-            .map(|s| s.as_str())
-            .unwrap_or(""),
+            .map(|s| s.get_str())
+            .unwrap_or_default()
+            .as_ref(),
     );
     ["model.json", "model3.json"]
         .iter()
@@ -43,9 +44,9 @@ fn show(args: Vec<String>, ctx: TextProcessContext) -> TextProcessResult {
     res.props.insert(
         "ch_models".to_string(),
         if models.is_empty() {
-            CH_DEFAULT.to_string()
+            CH_DEFAULT.into()
         } else {
-            models.join(",")
+            PropValue::from_iter(models)
         },
     );
     res
@@ -57,9 +58,9 @@ fn hide(args: Vec<String>, _ctx: TextProcessContext) -> TextProcessResult {
     res.props.insert(
         "ch_hide".to_string(),
         if args.is_empty() {
-            CH_ALL.to_string()
+            CH_ALL.into()
         } else {
-            args.join(",")
+            PropValue::from_iter(args)
         },
     );
     res
@@ -68,35 +69,37 @@ fn hide(args: Vec<String>, _ctx: TextProcessContext) -> TextProcessResult {
 #[export]
 fn process_action(mut ctx: ActionProcessContext) -> Action {
     let hide = ctx.action.props.remove("ch_hide");
-    if hide.as_deref() == Some(CH_ALL) {
+    if hide.as_ref().map(|hide| hide == CH_ALL).unwrap_or_default() {
         ctx.action.props.remove("ch_models");
     } else {
         let hide = hide
-            .as_ref()
-            .map(|hide| hide.split(',').collect::<HashSet<_>>())
-            .unwrap_or_default();
+            .map(|hide| hide.get_list())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|name| name.into_str())
+            .collect::<HashSet<_>>();
 
-        let models = ctx
+        let mut models = ctx
             .action
             .props
-            .entry("ch_models".to_string())
-            .or_insert_with_key(|key| {
+            .remove("ch_models")
+            .or_else(|| {
                 ctx.last_action
-                    .and_then(|act| act.props.get(key).cloned())
-                    .unwrap_or_default()
-            });
+                    .and_then(|act| act.props.get("ch_models").cloned())
+            })
+            .unwrap_or_default()
+            .get_list();
 
-        if models == CH_DEFAULT {
+        if models.len() == 1 && models[0] == CH_DEFAULT {
             if let Some(ch) = &ctx.action.ch_key {
-                *models = ch.clone()
+                models = vec![ch.as_str().into()];
             }
         }
 
-        *models = models
-            .split(',')
-            .filter(|name| !hide.contains(name))
-            .collect::<Vec<_>>()
-            .join(",");
+        models.retain(|name| !hide.contains(name.get_str().as_ref()));
+        ctx.action
+            .props
+            .insert("ch_models".to_string(), PropValue::List(models));
     }
     ctx.action
 }
@@ -104,14 +107,27 @@ fn process_action(mut ctx: ActionProcessContext) -> Action {
 #[export]
 fn process_game(mut ctx: GameProcessContext) -> GameProcessResult {
     if let Some(names) = ctx.props.remove("ch_names") {
-        for name in names.split(',') {
-            if let Some(path) = find_model(name, &ctx.root_path, &ctx.props) {
+        for name in names.get_list() {
+            let name = name.get_str();
+            if let Some(path) = find_model(&name, &ctx.root_path, &ctx.props) {
                 ctx.props.insert(
                     format!("ch_{}_model", name),
-                    path.to_string_lossy().into_owned(),
+                    path.to_string_lossy().into_owned().into(),
                 );
             }
         }
     }
+    for (id, ch) in &ctx.characters {
+        if let Some(model) = &ch.model {
+            ctx.props.insert(
+                format!("ch_{}_model", id),
+                ctx.root_path
+                    .join(model)
+                    .to_string_lossy()
+                    .into_owned()
+                    .into(),
+            );
+        }
+    }
     GameProcessResult { props: ctx.props }
 }
@@ -7,6 +7,7 @@ fn plugin_type() -> PluginType {
     PluginType::builder()
         .action()
         .text(["bg", "bgm", "efm", "video"])
+        .text_args([("bg", 1), ("bgm", 1), ("efm", 1), ("video", 1)])
         .game()
         .build()
 }
@@ -36,7 +37,7 @@ fn file(
     let mut res = TextProcessResult::default();
     if let Some(path) = find_exists(&args[0], base_dir, exs) {
         res.props
-            .insert(prop.to_string(), path.to_string_lossy().into_owned());
+            .insert(prop.to_string(), path.to_string_lossy().into_owned().into());
     }
     res
 }
@@ -53,8 +54,9 @@ fn file_ctx(
         ctx.root_path.join(
             ctx.game_props
                 .get(game_prop)
-                .map(|s| s.as_str())
-                .unwrap_or(""),
+                .map(|s| s.get_str())
+                .unwrap_or_default()
+                .as_ref(),
         ),
         prop,
         exs,
@@ -100,8 +102,9 @@ fn process_action(mut ctx: ActionProcessContext) -> Action {
             .join(
                 ctx.game_props
                     .get("voices")
-                    .map(|s| s.as_str())
-                    .unwrap_or(""),
+                    .map(|s| s.get_str())
+                    .unwrap_or_default()
+                    .as_ref(),
             )
             .join(&ctx.action.ctx.cur_para),
         "voice",
@@ -115,12 +118,16 @@ fn process_action(mut ctx: ActionProcessContext) -> Action {
 
 #[export]
 fn process_game(mut ctx: GameProcessContext) -> GameProcessResult {
-    let base_dir = ctx
-        .root_path
-        .join(ctx.props.get("bgs").map(|s| s.as_str()).unwrap_or(""));
+    let base_dir = ctx.root_path.join(
+        ctx.props
+            .get("bgs")
+            .map(|s| s.get_str())
+            .unwrap_or_default()
+            .as_ref(),
+    );
     if let Some(bg) = ctx.props.get_mut("bg") {
-        if let Some(path) = find_exists(bg, &base_dir, &["png", "jpg", "gif"]) {
-            *bg = path.to_string_lossy().into_owned();
+        if let Some(path) = find_exists(&bg.get_str(), &base_dir, &["png", "jpg", "gif"]) {
+            *bg = path.to_string_lossy().into_owned().into();
         }
     }
     GameProcessResult { props: ctx.props }
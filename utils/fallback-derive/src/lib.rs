@@ -1,106 +1,285 @@
 use proc_macro::TokenStream;
-use proc_macro2::Ident;
+use proc_macro2::{Ident, TokenStream as TokenStream2};
 use quote::quote;
 use syn::{
     parse::{Parse, Parser},
-    parse_macro_input, parse_str, Data, DeriveInput, Expr, FieldValue, Fields, Type,
+    parse_macro_input, parse_str, Attribute, Data, DeriveInput, Fields, Meta, NestedMeta, Type,
 };
 
-#[proc_macro_derive(FallbackSpec)]
-pub fn derive_fallback_spec(input: TokenStream) -> TokenStream {
-    let struct_input = parse_macro_input!(input as DeriveInput);
-    let struct_name = struct_input.ident;
-    let vis = struct_input.vis;
-    let (fallback_data_declare, data_idents, base_data_idents, some_exact, none_exact, construct) =
-        match struct_input.data {
-            Data::Struct(data) => match data.fields {
-                Fields::Named(fields) => {
-                    let fields = fields.named.into_iter().collect::<Vec<_>>();
-                    let fallback_data_declare = fields
-                        .iter()
-                        .map(|field| {
-                            let mut field = field.clone();
-                            let ty = field.ty.clone();
-                            field.ty = Type::parse
-                                .parse2(quote! {::fallback::Fallback<#ty>})
-                                .unwrap();
-                            field
-                        })
-                        .collect::<Vec<_>>();
-                    let data_idents = fields
-                        .iter()
-                        .map(|field| field.ident.clone().unwrap())
-                        .collect::<Vec<_>>();
-                    let base_data_idents = fields
-                        .iter()
-                        .map(|field| {
-                            parse_str::<Ident>(&format!("base_{}", field.ident.clone().unwrap()))
-                                .expect("Parse base idents failed")
-                        })
-                        .collect::<Vec<_>>();
-                    let some_exact = fields
-                        .iter()
-                        .map(|field| {
-                            parse_str::<Expr>(&format!(
-                                "Some(data.{})",
-                                field.ident.clone().unwrap()
-                            ))
-                            .expect("Parse some exact failed")
-                        })
-                        .collect::<Vec<_>>();
-                    let none_exact =
-                        std::iter::repeat(parse_str::<Expr>("None").expect("Parse None failed"))
-                            .take(fields.len())
-                            .collect::<Vec<_>>();
-                    let construct = fields
-                        .iter()
-                        .map(|field| {
-                            parse_str::<FieldValue>(&format!(
-                                "{0}: ::fallback::Fallback::new({0}, base_{0})",
-                                field.ident.clone().unwrap()
-                            ))
-                            .expect("Parse field value failed")
-                        })
-                        .collect::<Vec<_>>();
-                    (
-                        fallback_data_declare,
-                        data_idents,
-                        base_data_idents,
-                        some_exact,
-                        none_exact,
-                        construct,
-                    )
+/// The merge strategy of a field, specified by `#[fallback(...)]`.
+enum Strategy {
+    Fallback,
+    Base,
+    NonEmpty,
+    Merge,
+    Nested,
+}
+
+impl Strategy {
+    fn parse(attrs: &[Attribute]) -> Self {
+        let mut res = Self::Fallback;
+        for attr in attrs.iter().filter(|attr| attr.path.is_ident("fallback")) {
+            let list = match attr.parse_meta().expect("Parse fallback attribute failed") {
+                Meta::List(list) => list,
+                _ => panic!("Expected #[fallback(...)]"),
+            };
+            for nested in list.nested {
+                let name = match nested {
+                    NestedMeta::Meta(Meta::Path(path)) => path.get_ident().map(|i| i.to_string()),
+                    _ => None,
+                };
+                res = match name.as_deref() {
+                    Some("base") => Self::Base,
+                    Some("non_empty") => Self::NonEmpty,
+                    Some("merge") => Self::Merge,
+                    Some("nested") => Self::Nested,
+                    _ => panic!("Unknown fallback attribute"),
+                };
+            }
+        }
+        res
+    }
+
+    fn merge_expr(&self, data: &Ident) -> TokenStream2 {
+        match self {
+            Self::Fallback => quote! { #data.fallback()? },
+            Self::Base => quote! {
+                {
+                    let (data, base_data) = #data.unzip();
+                    base_data.or(data)?
                 }
-                _ => unimplemented!(),
             },
-            _ => unimplemented!(),
+            Self::NonEmpty => quote! { #data.and_any().unwrap_or_default() },
+            Self::Merge => quote! { #data.extend() },
+            Self::Nested => quote! { ::fallback::FallbackMerge::merge(#data)? },
+        }
+    }
+}
+
+/// The information of the fields of a struct or an enum variant.
+struct FieldsInfo {
+    /// The fields of the spec type.
+    spec_fields: Fields,
+    /// The names of named fields.
+    names: Option<Vec<Ident>>,
+    data_idents: Vec<Ident>,
+    chain_idents: Vec<Ident>,
+    strategies: Vec<Strategy>,
+}
+
+impl FieldsInfo {
+    fn new(fields: Fields) -> Self {
+        let names = match &fields {
+            Fields::Named(fields) => Some(
+                fields
+                    .named
+                    .iter()
+                    .map(|field| field.ident.clone().unwrap())
+                    .collect::<Vec<_>>(),
+            ),
+            _ => None,
         };
-    let fallback_struct_name = parse_str::<Ident>(&format!("Fallback{}", struct_name))
-        .expect("Parse fallback name failed");
-    let output = quote! {
-        #[doc(hidden)]
-        #vis struct #fallback_struct_name {
-            #(#fallback_data_declare ,)*
+        let data_idents = match &names {
+            Some(names) => names.clone(),
+            None => (0..fields.len())
+                .map(|i| parse_str::<Ident>(&format!("f{}", i)).expect("Parse data idents failed"))
+                .collect(),
+        };
+        let chain_idents = data_idents
+            .iter()
+            .map(|ident| {
+                parse_str::<Ident>(&format!("chain_{}", ident)).expect("Parse chain idents failed")
+            })
+            .collect();
+        let strategies = fields
+            .iter()
+            .map(|field| Strategy::parse(&field.attrs))
+            .collect();
+        let mut spec_fields = fields;
+        for field in spec_fields.iter_mut() {
+            // The attributes of other derives, like `serde`, are not applicable to the spec.
+            field.attrs.retain(|attr| attr.path.is_ident("doc"));
+            let ty = field.ty.clone();
+            field.ty = Type::parse
+                .parse2(quote! {::fallback::Fallback<#ty>})
+                .unwrap();
         }
+        Self {
+            spec_fields,
+            names,
+            data_idents,
+            chain_idents,
+            strategies,
+        }
+    }
 
-        impl FallbackSpec for #struct_name {
-            type SpecType = #fallback_struct_name;
+    /// Generates the braces or the parentheses with the values.
+    fn pattern(&self, values: impl IntoIterator<Item = TokenStream2>) -> TokenStream2 {
+        let values = values.into_iter();
+        match &self.names {
+            Some(names) => quote! { { #(#names: #values ,)* } },
+            None if self.data_idents.is_empty() => quote! {},
+            None => quote! { ( #(#values ,)* ) },
         }
+    }
 
-        impl From<::fallback::Fallback<#struct_name>> for #fallback_struct_name {
-            fn from(data: ::fallback::Fallback<#struct_name>) -> Self {
-                let (data, base_data) = data.unzip();
-                let (#(#data_idents ,)*) = match data {
-                    Some(data) => (#(#some_exact ,)*),
-                    None => (#(#none_exact ,)*),
-                };
-                let (#(#base_data_idents ,)*) = match base_data {
-                    Some(data) => (#(#some_exact ,)*),
-                    None => (#(#none_exact ,)*),
+    fn bindings(&self) -> TokenStream2 {
+        match &self.names {
+            Some(names) => quote! { { #(#names ,)* } },
+            None => self.pattern(self.data_idents.iter().map(|ident| quote! { #ident })),
+        }
+    }
+
+    fn spec_values(&self) -> TokenStream2 {
+        self.pattern(
+            self.chain_idents
+                .iter()
+                .map(|ident| quote! { ::fallback::Fallback::from_chain(#ident) }),
+        )
+    }
+
+    fn merge_values(&self) -> TokenStream2 {
+        self.pattern(
+            self.strategies
+                .iter()
+                .zip(&self.data_idents)
+                .map(|(strategy, ident)| strategy.merge_expr(ident)),
+        )
+    }
+}
+
+#[proc_macro_derive(FallbackSpec, attributes(fallback))]
+pub fn derive_fallback_spec(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = input.ident;
+    let vis = input.vis;
+    let spec_name =
+        parse_str::<Ident>(&format!("Fallback{}", name)).expect("Parse fallback name failed");
+    let (declare, from_body, merge_body) = match input.data {
+        Data::Struct(data) => {
+            let info = FieldsInfo::new(data.fields);
+            let spec_fields = &info.spec_fields;
+            let declare = match spec_fields {
+                Fields::Named(_) => quote! { #vis struct #spec_name #spec_fields },
+                _ => quote! { #vis struct #spec_name #spec_fields; },
+            };
+            let chain_idents = &info.chain_idents;
+            let data_idents = &info.data_idents;
+            let bindings = info.bindings();
+            let spec_values = info.spec_values();
+            let from_body = quote! {
+                #(let mut #chain_idents = vec![];)*
+                for data in data.into_chain() {
+                    match data {
+                        Some(#name #bindings) => {
+                            #(#chain_idents.push(Some(#data_idents));)*
+                        }
+                        None => {
+                            #(#chain_idents.push(None);)*
+                        }
+                    }
+                }
+                Self #spec_values
+            };
+            let merge_values = info.merge_values();
+            let merge_body = quote! {
+                if data.is_some() {
+                    let #spec_name #bindings = data.spec();
+                    Some(#name #merge_values)
+                } else {
+                    None
+                }
+            };
+            (declare, from_body, merge_body)
+        }
+        Data::Enum(data) => {
+            let variants = data
+                .variants
+                .into_iter()
+                .map(|v| (v.ident, FieldsInfo::new(v.fields)))
+                .collect::<Vec<_>>();
+            let spec_variants = variants.iter().map(|(ident, info)| {
+                let spec_fields = &info.spec_fields;
+                quote! { #ident #spec_fields }
+            });
+            let declare = quote! {
+                #vis enum #spec_name {
+                    #(#spec_variants ,)*
+                    FallbackNone,
+                }
+            };
+            // The variant is determined by the most specific data,
+            // and the data of other variants are treated as `None`.
+            let from_arms = variants.iter().map(|(ident, info)| {
+                let chain_idents = &info.chain_idents;
+                let data_idents = &info.data_idents;
+                let bindings = info.bindings();
+                let spec_values = info.spec_values();
+                let collect = if chain_idents.is_empty() {
+                    quote! {}
+                } else {
+                    quote! {
+                        #(let mut #chain_idents = vec![];)*
+                        for data in chain {
+                            match data {
+                                Some(#name::#ident #bindings) => {
+                                    #(#chain_idents.push(Some(#data_idents));)*
+                                }
+                                _ => {
+                                    #(#chain_idents.push(None);)*
+                                }
+                            }
+                        }
+                    }
                 };
-                Self {
-                    #(#construct ,)*
+                quote! {
+                    Some(#name::#ident { .. }) => {
+                        #collect
+                        Self::#ident #spec_values
+                    }
+                }
+            });
+            let from_body = quote! {
+                let chain = data.into_chain();
+                match chain.iter().flatten().next() {
+                    #(#from_arms)*
+                    None => Self::FallbackNone,
+                }
+            };
+            let merge_arms = variants.iter().map(|(ident, info)| {
+                let bindings = info.bindings();
+                let merge_values = info.merge_values();
+                quote! {
+                    #spec_name::#ident #bindings => Some(#name::#ident #merge_values),
+                }
+            });
+            let merge_body = quote! {
+                match data.spec() {
+                    #(#merge_arms)*
+                    #spec_name::FallbackNone => None,
                 }
+            };
+            (declare, from_body, merge_body)
+        }
+        _ => unimplemented!(),
+    };
+    let output = quote! {
+        #[doc(hidden)]
+        #declare
+
+        impl ::fallback::FallbackSpec for #name {
+            type SpecType = #spec_name;
+        }
+
+        impl From<::fallback::Fallback<#name>> for #spec_name {
+            fn from(data: ::fallback::Fallback<#name>) -> Self {
+                #from_body
+            }
+        }
+
+        impl ::fallback::FallbackMerge for #name {
+            fn merge(data: ::fallback::Fallback<Self>) -> Option<Self> {
+                #merge_body
             }
         }
     };
@@ -6,8 +6,9 @@
 #![warn(missing_docs)]
 #![deny(unsafe_code)]
 
-/// Stores two [`Option`], and provides functionality to fallback.
+/// Stores a chain of [`Option`], and provides functionality to fallback.
 ///
+/// The chain is ordered from the most specific data to the base data.
 /// Basically, you provides a function returns [`Option`],
 /// and [`Fallback`] handles the fallback.
 /// ```
@@ -28,54 +29,73 @@
 /// let s = fallback.and_then(|s| if s.len() > 3 { Some(s) } else { None });
 /// assert_eq!(s, Some("123456".to_string()));
 /// ```
+/// The chain could be longer than two:
+/// ```
+/// # use fallback::Fallback;
+/// let fallback = Fallback::from_chain([None, Some("zh-Hans"), Some("en")]);
+/// assert_eq!(fallback.fallback(), Some("zh-Hans"));
+/// ```
 pub struct Fallback<T> {
-    data: Option<T>,
-    base_data: Option<T>,
+    chain: Vec<Option<T>>,
 }
 
 impl<T> Fallback<T> {
-    /// Creates a new [`Fallback`].
-    pub const fn new(data: Option<T>, base_data: Option<T>) -> Self {
-        Self { data, base_data }
+    /// Creates a new [`Fallback`] with the data and the base data.
+    pub fn new(data: Option<T>, base_data: Option<T>) -> Self {
+        Self::from_chain([data, base_data])
     }
 
-    /// Returns `false` if both `data` and `base_data` are [`None`].
-    pub const fn is_some(&self) -> bool {
-        self.data.is_some() || self.base_data.is_some()
+    /// Creates a new [`Fallback`] from a chain,
+    /// from the most specific data to the base data.
+    pub fn from_chain(chain: impl IntoIterator<Item = Option<T>>) -> Self {
+        Self {
+            chain: chain.into_iter().collect(),
+        }
+    }
+
+    /// Returns `false` if all data in the chain are [`None`].
+    pub fn is_some(&self) -> bool {
+        self.chain.iter().any(|d| d.is_some())
     }
 
     /// Converts from `&Fallback<T>` to `Fallback<&T>`.
-    pub const fn as_ref(&self) -> Fallback<&T> {
-        Fallback::new(self.data.as_ref(), self.base_data.as_ref())
+    pub fn as_ref(&self) -> Fallback<&T> {
+        Fallback::from_chain(self.chain.iter().map(|d| d.as_ref()))
     }
 
     /// Fallbacks the data or part of data.
     pub fn and_then<V>(self, mut f: impl FnMut(T) -> Option<V>) -> Option<V> {
-        self.data
-            .and_then(&mut f)
-            .or_else(|| self.base_data.and_then(&mut f))
+        self.chain.into_iter().find_map(|d| d.and_then(&mut f))
     }
 
     /// Fallbacks the total data.
     pub fn fallback(self) -> Option<T> {
-        self.data.or(self.base_data)
+        self.chain.into_iter().flatten().next()
     }
 
     /// Maps to a new [`Fallback`].
     pub fn map<V>(self, mut f: impl FnMut(T) -> V) -> Fallback<V> {
-        Fallback::new(self.data.map(&mut f), self.base_data.map(&mut f))
+        Fallback::from_chain(self.chain.into_iter().map(|d| d.map(&mut f)))
     }
 
-    /// Exacts the `data` and `base_data`.
-    pub fn unzip(self) -> (Option<T>, Option<T>) {
-        (self.data, self.base_data)
+    /// Exacts the data and the base data.
+    ///
+    /// The data is the first one available in the chain except the base data.
+    pub fn unzip(mut self) -> (Option<T>, Option<T>) {
+        let base_data = self.chain.pop().flatten();
+        (self.fallback(), base_data)
+    }
+
+    /// Exacts the chain, from the most specific data to the base data.
+    pub fn into_chain(self) -> Vec<Option<T>> {
+        self.chain
     }
 }
 
 impl<T> Fallback<Option<T>> {
     /// Converts from `Fallback<Option<T>>` to `Fallback<T>`.
     pub fn flatten(self) -> Fallback<T> {
-        Fallback::new(self.data.flatten(), self.base_data.flatten())
+        Fallback::from_chain(self.chain.into_iter().map(|d| d.flatten()))
     }
 }
 
@@ -102,6 +122,12 @@ impl<K, V> IsEmpty2 for HashMap<K, V> {
     }
 }
 
+impl<T: IsEmpty2> IsEmpty2 for Option<T> {
+    fn is_empty2(&self) -> bool {
+        self.as_ref().map(|t| t.is_empty2()).unwrap_or(true)
+    }
+}
+
 impl<T: IsEmpty2> Fallback<T> {
     /// Treats the empty container as [`None`] and fallbacks.
     pub fn and_any(self) -> Option<T> {
@@ -109,30 +135,45 @@ impl<T: IsEmpty2> Fallback<T> {
     }
 }
 
+impl<T: Default + IntoIterator + Extend<T::Item>> Fallback<T> {
+    /// Extends the data from the base one to the most specific one.
+    ///
+    /// For maps, the entries in the more specific data override the ones in the base data.
+    /// ```
+    /// # use fallback::Fallback;
+    /// # use std::collections::HashMap;
+    /// let data = HashMap::from([("a", 1)]);
+    /// let base_data = HashMap::from([("a", 2), ("b", 3)]);
+    /// let map = Fallback::new(Some(data), Some(base_data)).extend();
+    /// assert_eq!(map, HashMap::from([("a", 1), ("b", 3)]));
+    /// ```
+    pub fn extend(self) -> T {
+        let mut res = T::default();
+        for data in self.chain.into_iter().rev().flatten() {
+            res.extend(data);
+        }
+        res
+    }
+}
+
 impl<T> From<Fallback<T>> for Option<T> {
     fn from(f: Fallback<T>) -> Self {
-        if f.data.is_some() {
-            f.data
-        } else {
-            f.base_data
-        }
+        f.fallback()
     }
 }
 
 #[doc(hidden)]
 pub struct FallbackIter<A> {
-    data: A,
-    base_data: A,
+    chain: Vec<A>,
 }
 
 impl<A: Iterator> Iterator for FallbackIter<A> {
     type Item = Fallback<A::Item>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let d = self.data.next();
-        let based = self.base_data.next();
-        if d.is_some() || based.is_some() {
-            Some(Fallback::new(d, based))
+        let f = Fallback::from_chain(self.chain.iter_mut().map(|it| it.next()));
+        if f.is_some() {
+            Some(f)
         } else {
             None
         }
@@ -146,8 +187,11 @@ impl<T> IntoIterator for Fallback<Vec<T>> {
 
     fn into_iter(self) -> Self::IntoIter {
         FallbackIter {
-            data: self.data.unwrap_or_default().into_iter(),
-            base_data: self.base_data.unwrap_or_default().into_iter(),
+            chain: self
+                .chain
+                .into_iter()
+                .map(|d| d.unwrap_or_default().into_iter())
+                .collect(),
         }
     }
 }
@@ -182,22 +226,49 @@ impl<T> IntoIterator for Fallback<Vec<T>> {
 ///
 /// impl From<Fallback<Foo>> for FallbackFoo {
 ///     fn from(data: Fallback<Foo>) -> Self {
-///         let (data, base_data) = data.unzip();
-///         let (data1, data2) = match data {
-///             Some(data) => (Some(data.data1), Some(data.data2)),
-///             None => (None, None),
-///         };
-///         let (base_data1, base_data2) = match base_data {
-///             Some(data) => (Some(data.data1), Some(data.data2)),
-///             None => (None, None),
-///         };
+///         let mut chain_data1 = vec![];
+///         let mut chain_data2 = vec![];
+///         for data in data.into_chain() {
+///             match data {
+///                 Some(Foo { data1, data2 }) => {
+///                     chain_data1.push(Some(data1));
+///                     chain_data2.push(Some(data2));
+///                 }
+///                 None => {
+///                     chain_data1.push(None);
+///                     chain_data2.push(None);
+///                 }
+///             }
+///         }
 ///         Self {
-///             data1: Fallback::new(data1, base_data1),
-///             data2: Fallback::new(data2, base_data2),
+///             data1: Fallback::from_chain(chain_data1),
+///             data2: Fallback::from_chain(chain_data2),
 ///         }
 ///     }
 /// }
 /// ```
+/// The tuple structs are supported, and the fields are named `0`, `1` and so on.
+///
+/// For an enum, the specialized type is an enum with the same variants,
+/// and an additional `FallbackNone` variant for an empty chain.
+/// The variant is determined by the most specific data,
+/// and the data of other variants in the chain are treated as [`None`].
+/// ```
+/// # use fallback::*;
+/// #[derive(FallbackSpec)]
+/// enum Foo {
+///     Int(i32),
+///     Str(String),
+/// }
+///
+/// let data = Fallback::from_chain([None, Some(Foo::Int(1)), Some(Foo::Str("a".into())), Some(Foo::Int(2))]);
+/// match data.spec() {
+///     FallbackFoo::Int(i) => assert_eq!(i.into_chain(), [None, Some(1), None, Some(2)]),
+///     _ => unreachable!(),
+/// }
+/// ```
+///
+/// The derive macro also implements [`FallbackMerge`].
 pub trait FallbackSpec: Sized {
     /// The specialized fallback type.
     type SpecType: From<Fallback<Self>>;
@@ -210,6 +281,72 @@ impl<T: FallbackSpec> Fallback<T> {
     }
 }
 
+/// This trait merges the data in the chain of a [`Fallback`] into one.
+///
+/// It is implemented by the [`FallbackSpec`] derive macro.
+/// The fields are merged by the strategies specified with the `fallback` attribute:
+///
+/// | Attribute                | Strategy                                                      |
+/// | ------------------------ | ------------------------------------------------------------- |
+/// | (none)                   | The most specific data, like [`Fallback::fallback`].          |
+/// | `#[fallback(base)]`      | The base data first.                                          |
+/// | `#[fallback(non_empty)]` | The most specific non-empty data, like [`Fallback::and_any`]. |
+/// | `#[fallback(merge)]`     | Extends the data, like [`Fallback::extend`].                  |
+/// | `#[fallback(nested)]`    | Merges the data recursively with [`FallbackMerge`].           |
+///
+/// ```
+/// # use fallback::*;
+/// # use std::collections::HashMap;
+/// #[derive(Debug, PartialEq, FallbackSpec)]
+/// struct Foo {
+///     #[fallback(non_empty)]
+///     text: String,
+///     #[fallback(base)]
+///     enabled: bool,
+///     #[fallback(merge)]
+///     props: HashMap<String, i32>,
+/// }
+///
+/// let data = Foo {
+///     text: String::new(),
+///     enabled: true,
+///     props: HashMap::from([("a".to_string(), 1)]),
+/// };
+/// let base_data = Foo {
+///     text: "Hello".to_string(),
+///     enabled: false,
+///     props: HashMap::from([("b".to_string(), 2)]),
+/// };
+/// let foo = Fallback::new(Some(data), Some(base_data)).merge();
+/// assert_eq!(
+///     foo,
+///     Some(Foo {
+///         text: "Hello".to_string(),
+///         enabled: false,
+///         props: HashMap::from([("a".to_string(), 1), ("b".to_string(), 2)]),
+///     })
+/// );
+/// ```
+pub trait FallbackMerge: Sized {
+    /// Merges the data in the chain.
+    /// Returns [`None`] if all data are [`None`].
+    fn merge(data: Fallback<Self>) -> Option<Self>;
+}
+
+/// The elements with the same index are merged.
+impl<T: FallbackMerge> FallbackMerge for Vec<T> {
+    fn merge(data: Fallback<Self>) -> Option<Self> {
+        Some(data.into_iter().filter_map(T::merge).collect())
+    }
+}
+
+impl<T: FallbackMerge> Fallback<T> {
+    /// Merges the data in the chain.
+    pub fn merge(self) -> Option<T> {
+        T::merge(self)
+    }
+}
+
 use std::collections::HashMap;
 
 pub use fallback_derive::FallbackSpec;
@@ -228,4 +365,22 @@ mod test {
         let f = Fallback::new(None, Some(100));
         assert_eq!(Option::from(f), Some(100));
     }
+
+    #[test]
+    fn chain() {
+        let f = Fallback::from_chain([None, Some(vec![1, 2]), Some(vec![3, 4, 5])]);
+        assert_eq!(
+            f.as_ref()
+                .map(|v| v.len())
+                .and_then(|l| (l > 2).then_some(l)),
+            Some(3)
+        );
+        assert_eq!(
+            f.into_iter().map(|f| f.fallback()).collect::<Vec<_>>(),
+            [Some(1), Some(2), Some(5)]
+        );
+
+        let f = Fallback::from_chain([None, Some(1), Some(2), Some(3)]);
+        assert_eq!(f.unzip(), (Some(1), Some(3)));
+    }
 }
@@ -19,3 +19,60 @@ fn derive() {
     assert_eq!(data.data1.unzip(), (None, Some(123)));
     assert_eq!(data.data2.unzip(), (None, Some("Hello".to_string())));
 }
+
+#[test]
+fn chain() {
+    let data = Fallback::from_chain([
+        None,
+        Some(Foo {
+            data1: 1,
+            data2: String::new(),
+        }),
+        Some(Foo {
+            data1: 2,
+            data2: "Base".to_string(),
+        }),
+    ]);
+    let data = data.spec();
+
+    assert_eq!(data.data1.fallback(), Some(1));
+    assert_eq!(data.data2.and_any(), Some("Base".to_string()));
+}
+
+#[derive(Debug, PartialEq, FallbackSpec)]
+struct Bar(#[fallback(non_empty)] Vec<i32>, #[fallback(base)] bool);
+
+#[test]
+fn tuple() {
+    let data = Fallback::new(Some(Bar(vec![], true)), Some(Bar(vec![1], false)));
+
+    assert_eq!(data.merge(), Some(Bar(vec![1], false)));
+}
+
+#[derive(Debug, PartialEq, FallbackSpec)]
+enum Baz {
+    Foo { data: i32 },
+    Bar(#[fallback(nested)] Vec<Bar>),
+    None,
+}
+
+#[test]
+fn enum_merge() {
+    let data = Fallback::from_chain([
+        None,
+        Some(Baz::Bar(vec![Bar(vec![], true), Bar(vec![2], true)])),
+        Some(Baz::Foo { data: 1 }),
+        Some(Baz::Bar(vec![Bar(vec![1], false)])),
+    ]);
+
+    assert_eq!(
+        data.merge(),
+        Some(Baz::Bar(vec![Bar(vec![1], false), Bar(vec![2], true)]))
+    );
+
+    let data = Fallback::new(Some(Baz::None), Some(Baz::Foo { data: 1 }));
+    assert_eq!(data.merge(), Some(Baz::None));
+
+    let data = Fallback::<Baz>::new(None, None);
+    assert_eq!(data.merge(), None);
+}
@@ -4,6 +4,7 @@
 #![deny(unsafe_code)]
 
 use fallback::{FallbackSpec, IsEmpty2};
+use gal_primitive::PropValue;
 use gal_script::{Program, RawValue};
 use serde::{Deserialize, Serialize};
 use std::{
@@ -61,6 +62,11 @@ pub struct PluginType {
     /// The game plugin.
     /// This plugin processes the game properties after it is loaded.
     pub game: bool,
+    /// The argument counts of the text commands.
+    /// They are checked before running the game,
+    /// and the commands not declared are not checked.
+    #[serde(default)]
+    pub text_args: HashMap<String, usize>,
 }
 
 impl PluginType {
@@ -90,6 +96,12 @@ impl PluginTypeBuilder {
         self
     }
 
+    /// Declares the argument counts of the text commands.
+    pub fn text_args(mut self, args: impl IntoIterator<Item = (impl Into<String>, usize)>) -> Self {
+        self.data.text_args = args.into_iter().map(|(cmd, n)| (cmd.into(), n)).collect();
+        self
+    }
+
     /// A game plugin.
     pub fn game(mut self) -> Self {
         self.data.game = true;
@@ -238,8 +250,18 @@ pub struct RawContext {
     pub cur_para: String,
     /// Current text index.
     pub cur_act: usize,
+    /// The stable key of the text at `cur_act`.
+    /// It is used to resolve the position when the game config changes.
+    #[serde(default)]
+    pub act_key: Option<String>,
     /// Current local variables.
     pub locals: VarMap,
+    /// The visit counts of the paragraphs in this playthrough, indexed by tag.
+    #[serde(default)]
+    pub visits: HashMap<String, usize>,
+    /// The count of the texts run before the current one.
+    #[serde(default)]
+    pub turns: usize,
 }
 
 /// The full action information in one line of config.
@@ -249,32 +271,76 @@ pub struct Action {
     /// The context snapshot.
     pub ctx: RawContext,
     /// The full texts.
+    #[fallback(non_empty)]
     pub line: ActionLines,
     #[doc(hidden)]
+    #[fallback(non_empty)]
     pub line_params: Vec<RawValue>,
     /// The key of current character.
+    #[fallback(non_empty)]
     pub ch_key: Option<String>,
     /// The current character.
+    #[fallback(non_empty)]
     pub character: Option<String>,
     /// The title of current paragraph.
+    #[fallback(non_empty)]
     pub para_title: Option<String>,
     /// The switches.
+    #[fallback(nested)]
     pub switches: Vec<Switch>,
     /// The actions of switches.
+    #[fallback(nested)]
     pub switch_actions: Vec<Program>,
     /// The other custom properties.
-    pub props: HashMap<String, String>,
+    #[fallback(merge)]
+    pub props: HashMap<String, PropValue>,
+    /// The local variables changed by the scripts of the action, with the values before them.
+    /// The ones not existing before are [`RawValue::Unit`].
+    /// The action is re-rendered with them applied to the locals in `ctx`,
+    /// so that the side effects are not applied again.
+    /// It is [`None`] for the actions saved by the old versions.
+    #[serde(default)]
+    pub prev_locals: Option<VarMap>,
 }
 
 /// One switch in the switches of an [`Action`].
 #[derive(Debug, Default, Clone, Serialize, Deserialize, FallbackSpec)]
 pub struct Switch {
     /// The switch text.
+    #[fallback(non_empty)]
     pub text: String,
     /// Whether the switch is enabled.
+    #[fallback(base)]
     pub enabled: bool,
 }
 
+/// A character declared in the game config.
+///
+/// The display names are indexed by locale tags,
+/// and the paths are relative to the root path of the game profile.
+///
+/// The fields are never skipped in serialization,
+/// because the plugin arguments are serialized as arrays.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Character {
+    /// The display names, indexed by locale.
+    #[serde(default)]
+    pub names: HashMap<String, String>,
+    /// The color of the name, in CSS syntax.
+    #[serde(default)]
+    pub color: Option<String>,
+    /// The directory of the voices.
+    #[serde(default)]
+    pub voice: Option<PathBuf>,
+    /// The path of the model.
+    #[serde(default)]
+    pub model: Option<PathBuf>,
+    /// The sprites, indexed by name.
+    #[serde(default)]
+    pub sprites: HashMap<String, PathBuf>,
+}
+
 /// The argument to action plugin.
 ///
 /// Every action plugin should implement `process_action`:
@@ -292,9 +358,11 @@ pub struct ActionProcessContext {
     /// The root path of the game profile.
     pub root_path: PathBuf,
     /// The global properties of the game profile.
-    pub game_props: HashMap<String, String>,
+    pub game_props: HashMap<String, PropValue>,
     /// The frontend type.
     pub frontend: FrontendType,
+    /// The declared characters, indexed by id.
+    pub characters: HashMap<String, Character>,
     /// The previous action in the history.
     /// It is used if some properties need to inherit.
     pub last_action: Option<Action>,
@@ -306,8 +374,9 @@ pub struct ActionProcessContext {
 #[doc(hidden)]
 pub struct ActionProcessContextRef<'a> {
     pub root_path: &'a Path,
-    pub game_props: &'a HashMap<String, String>,
+    pub game_props: &'a HashMap<String, PropValue>,
     pub frontend: FrontendType,
+    pub characters: &'a HashMap<String, Character>,
     pub last_action: Option<&'a Action>,
     pub action: &'a Action,
 }
@@ -335,7 +404,7 @@ pub struct TextProcessContext {
     /// The root path of the game profile.
     pub root_path: PathBuf,
     /// The global properties of the game profile.
-    pub game_props: HashMap<String, String>,
+    pub game_props: HashMap<String, PropValue>,
     /// The frontend type.
     pub frontend: FrontendType,
 }
@@ -344,7 +413,7 @@ pub struct TextProcessContext {
 #[doc(hidden)]
 pub struct TextProcessContextRef<'a> {
     pub root_path: &'a Path,
-    pub game_props: &'a HashMap<String, String>,
+    pub game_props: &'a HashMap<String, PropValue>,
     pub frontend: FrontendType,
 }
 
@@ -355,7 +424,7 @@ pub struct TextProcessResult {
     /// The lines to append.
     pub line: ActionLines,
     /// The custom properties to update.
-    pub props: HashMap<String, String>,
+    pub props: HashMap<String, PropValue>,
 }
 
 /// The argument to game plugin.
@@ -379,7 +448,9 @@ pub struct GameProcessContext {
     /// The root path of the game profile.
     pub root_path: PathBuf,
     /// The global properties of the game.
-    pub props: HashMap<String, String>,
+    pub props: HashMap<String, PropValue>,
+    /// The declared characters, indexed by id.
+    pub characters: HashMap<String, Character>,
 }
 
 #[derive(Debug, Serialize)]
@@ -388,7 +459,8 @@ pub struct GameProcessContextRef<'a> {
     pub title: &'a str,
     pub author: &'a str,
     pub root_path: &'a Path,
-    pub props: &'a HashMap<String, String>,
+    pub props: &'a HashMap<String, PropValue>,
+    pub characters: &'a HashMap<String, Character>,
 }
 
 /// The result of game plugins.
@@ -396,5 +468,5 @@ pub struct GameProcessContextRef<'a> {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GameProcessResult {
     /// The updated properties.
-    pub props: HashMap<String, String>,
+    pub props: HashMap<String, PropValue>,
 }
@@ -2,13 +2,14 @@
 //!
 //! This crate provides the primitive type [`RawValue`].
 //! It is used by scripts in `gal` project.
+//! The structured [`PropValue`] is used by the properties of games and actions.
 //! The value operation and type conversion are handled in `gal-runtime`.
 
 #![warn(missing_docs)]
 #![deny(unsafe_code)]
 
 use serde::{Deserialize, Serialize};
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::HashMap};
 
 /// The basic and only type used in scripts.
 /// ```
@@ -216,6 +217,262 @@ impl Serialize for RawValue {
     }
 }
 
+/// The structured value of a property.
+///
+/// A string like `a,b,c` is treated as a list, to be compatible with the old string properties.
+/// A float is kept as its string representation, because [`RawValue`] doesn't support it.
+/// ```
+/// # use gal_primitive::{PropValue, RawValue};
+/// assert_eq!(serde_yaml::from_str::<PropValue>("123").unwrap(), PropValue::from(RawValue::Num(123)));
+/// assert_eq!(serde_yaml::from_str::<PropValue>("0.25").unwrap(), "0.25");
+/// assert_eq!(
+///     serde_yaml::from_str::<PropValue>("[a, b]").unwrap(),
+///     PropValue::from_iter(["a", "b"])
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PropValue {
+    /// A primitive value.
+    Value(RawValue),
+    /// A list of values.
+    List(Vec<PropValue>),
+    /// A map of values.
+    Map(HashMap<String, PropValue>),
+}
+
+impl Default for PropValue {
+    fn default() -> Self {
+        Self::Value(RawValue::Unit)
+    }
+}
+
+impl PropValue {
+    /// Gets a boolean from the value.
+    /// A list or a map converts to `false` if and only if it's empty.
+    /// See [`RawValue::get_bool`].
+    pub fn get_bool(&self) -> bool {
+        match self {
+            Self::Value(v) => v.get_bool(),
+            Self::List(list) => !list.is_empty(),
+            Self::Map(map) => !map.is_empty(),
+        }
+    }
+
+    /// Gets a number from the value.
+    /// A list or a map converts to its length.
+    /// See [`RawValue::get_num`].
+    pub fn get_num(&self) -> i64 {
+        match self {
+            Self::Value(v) => v.get_num(),
+            Self::List(list) => list.len() as i64,
+            Self::Map(map) => map.len() as i64,
+        }
+    }
+
+    /// Gets a string from the value.
+    /// A list converts to the items joined with commas,
+    /// and a map converts to empty string.
+    /// See [`RawValue::get_str`].
+    ///
+    /// ```
+    /// # use gal_primitive::PropValue;
+    /// assert_eq!(PropValue::from("hello").get_str(), "hello");
+    /// assert_eq!(PropValue::from_iter(["a", "b"]).get_str(), "a,b");
+    /// ```
+    pub fn get_str(&self) -> Cow<'_, str> {
+        match self {
+            Self::Value(v) => v.get_str(),
+            Self::List(list) => list
+                .iter()
+                .map(|v| v.get_str())
+                .collect::<Vec<_>>()
+                .join(",")
+                .into(),
+            Self::Map(_) => Cow::default(),
+        }
+    }
+
+    /// Gets a string from the value.
+    /// See [`PropValue::get_str`].
+    pub fn into_str(self) -> String {
+        match self {
+            Self::Value(v) => v.into_str(),
+            _ => self.get_str().into_owned(),
+        }
+    }
+
+    /// Gets a list from the value:
+    /// * A [`RawValue::Unit`] converts to an empty list.
+    /// * A [`RawValue::Str`] is split by commas, and the empty items are skipped.
+    /// * A map converts to an empty list.
+    /// * Other values convert to a list with only itself.
+    ///
+    /// ```
+    /// # use gal_primitive::PropValue;
+    /// assert_eq!(PropValue::from("a,b").get_list(), PropValue::from_iter(["a", "b"]).get_list());
+    /// assert!(PropValue::default().get_list().is_empty());
+    /// ```
+    pub fn get_list(&self) -> Vec<PropValue> {
+        match self {
+            Self::Value(RawValue::Unit) | Self::Map(_) => vec![],
+            Self::Value(RawValue::Str(s)) => s
+                .split(',')
+                .filter(|s| !s.is_empty())
+                .map(Self::from)
+                .collect(),
+            Self::Value(_) => vec![self.clone()],
+            Self::List(list) => list.clone(),
+        }
+    }
+}
+
+impl From<RawValue> for PropValue {
+    fn from(v: RawValue) -> Self {
+        Self::Value(v)
+    }
+}
+
+impl From<String> for PropValue {
+    fn from(s: String) -> Self {
+        Self::Value(RawValue::Str(s))
+    }
+}
+
+impl From<&str> for PropValue {
+    fn from(s: &str) -> Self {
+        Self::from(s.to_string())
+    }
+}
+
+impl From<i64> for PropValue {
+    fn from(n: i64) -> Self {
+        Self::Value(RawValue::Num(n))
+    }
+}
+
+impl From<bool> for PropValue {
+    fn from(b: bool) -> Self {
+        Self::Value(RawValue::Bool(b))
+    }
+}
+
+impl<T: Into<PropValue>> FromIterator<T> for PropValue {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::List(iter.into_iter().map(|v| v.into()).collect())
+    }
+}
+
+impl PartialEq<str> for PropValue {
+    fn eq(&self, other: &str) -> bool {
+        matches!(self, Self::Value(RawValue::Str(s)) if s == other)
+    }
+}
+
+impl PartialEq<&str> for PropValue {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl<'de> Deserialize<'de> for PropValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::{Error, MapAccess, SeqAccess};
+
+        struct PropVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for PropVisitor {
+            type Value = PropValue;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a unit, boolean, number, string, list or map value")
+            }
+
+            fn visit_unit<E>(self) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                Ok(RawValue::Unit.into())
+            }
+
+            fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                Ok(v.into())
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                Ok(v.into())
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                i64::try_from(v)
+                    .map(|v| v.into())
+                    .map_err(|_| E::custom(format!("number {} is too large", v)))
+            }
+
+            fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                Ok(v.to_string().into())
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                Ok(v.into())
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let mut list = vec![];
+                while let Some(v) = seq.next_element()? {
+                    list.push(v);
+                }
+                Ok(PropValue::List(list))
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut res = HashMap::new();
+                while let Some((key, value)) = map.next_entry()? {
+                    res.insert(key, value);
+                }
+                Ok(PropValue::Map(res))
+            }
+        }
+        deserializer.deserialize_any(PropVisitor)
+    }
+}
+
+impl Serialize for PropValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            Self::Value(v) => v.serialize(serializer),
+            Self::List(list) => serializer.collect_seq(list),
+            Self::Map(map) => serializer.collect_map(map),
+        }
+    }
+}
+
 #[cfg(feature = "rt-format")]
 use rt_format::{Format, FormatArgument, Specifier};
 
@@ -347,4 +604,41 @@ mod test {
             "aaa\n"
         );
     }
+
+    #[test]
+    fn serde_prop() {
+        assert_eq!(
+            serde_yaml::from_str::<PropValue>("~").unwrap(),
+            PropValue::default()
+        );
+        assert_eq!(serde_yaml::from_str::<PropValue>("1.5").unwrap(), "1.5");
+        assert_eq!(
+            serde_yaml::from_str::<PropValue>("[1, [true]]").unwrap(),
+            PropValue::List(vec![1.into(), PropValue::from_iter([true])])
+        );
+        assert_eq!(
+            serde_yaml::from_str::<PropValue>("{ a: b }").unwrap(),
+            PropValue::Map(HashMap::from([("a".to_string(), "b".into())]))
+        );
+        assert!(serde_yaml::from_str::<PropValue>("18446744073709551615").is_err());
+
+        assert_eq!(
+            serde_yaml::to_string(&PropValue::from_iter(["a", "b"])).unwrap(),
+            "- a\n- b\n"
+        );
+        assert_eq!(
+            serde_yaml::to_string(&PropValue::from(RawValue::Num(1))).unwrap(),
+            "1\n"
+        );
+    }
+
+    #[test]
+    fn prop_compat() {
+        let old = PropValue::from("Haru,Hiyori");
+        let new = PropValue::from_iter(["Haru", "Hiyori"]);
+        assert_eq!(old.get_list(), new.get_list());
+        assert_eq!(old.get_str(), new.get_str());
+        assert_eq!(PropValue::from(1).get_list(), vec![PropValue::from(1)]);
+        assert_eq!(PropValue::from_iter(["a"]).get_num(), 1);
+    }
 }
@@ -0,0 +1,327 @@
+use crate::{plugin::Runtime, script::INTRINSICS, *};
+use gal_script::{BinaryOp, Command, Expr, Line, Program, Ref, Text, TextParser};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Checks the semantics of the texts against the loaded plugins and resources.
+pub(crate) struct Checker<'a> {
+    game: &'a Game,
+    runtime: &'a Runtime,
+    /// The variables written in the paragraphs of any locale, by tag.
+    para_written: HashMap<&'a str, HashSet<String>>,
+}
+
+/// Gets all texts in a paragraph in the order they run, with the text index.
+fn para_texts(para: &Paragraph) -> impl Iterator<Item = (Option<usize>, Text)> + '_ {
+    para.on_enter
+        .iter()
+        .map(|t| (None, t))
+        .chain(
+            para.texts
+                .iter()
+                .enumerate()
+                .map(|(index, t)| (Some(index), t)),
+        )
+        .chain(
+            [&para.on_exit, &para.next]
+                .into_iter()
+                .flatten()
+                .map(|t| (None, t)),
+        )
+        .filter_map(|(index, t)| TextParser::new(t).parse().ok().map(|t| (index, t)))
+}
+
+/// Gets all programs in a text in the order they run.
+/// The condition of a switch runs before its action.
+fn text_programs(text: &Text) -> impl Iterator<Item = &Program> {
+    text.0.iter().flat_map(|line| match line {
+        Line::Cmd(Command::Exec(p)) => vec![p],
+        Line::Cmd(Command::Switch {
+            action, enabled, ..
+        }) => enabled.iter().chain(std::iter::once(action)).collect(),
+        _ => vec![],
+    })
+}
+
+fn collect_written(expr: &Expr, written: &mut HashSet<String>) {
+    match expr {
+        Expr::Unary(_, e) => collect_written(e, written),
+        Expr::Binary(lhs, op, rhs) => {
+            if let (BinaryOp::Assign | BinaryOp::Inplace(_), Expr::Ref(Ref::Ctx(name))) =
+                (op, lhs.as_ref())
+            {
+                written.insert(name.clone());
+            }
+            collect_written(lhs, written);
+            collect_written(rhs, written);
+        }
+        Expr::Call(_, _, args) => args.iter().for_each(|e| collect_written(e, written)),
+        _ => {}
+    }
+}
+
+impl<'a> Checker<'a> {
+    pub fn new(game: &'a Game, runtime: &'a Runtime) -> Self {
+        let mut para_written = HashMap::<&str, HashSet<String>>::new();
+        for para in game.paras.values().flatten() {
+            let written = para_written.entry(para.tag.as_str()).or_default();
+            for (_, text) in para_texts(para) {
+                for p in text_programs(&text) {
+                    p.0.iter().for_each(|e| collect_written(e, written));
+                }
+            }
+        }
+        Self {
+            game,
+            runtime,
+            para_written,
+        }
+    }
+
+    /// Gets the variables which may have been written before entering the paragraphs,
+    /// following the paragraph graph of the locale from the start paragraph.
+    ///
+    /// A dynamic `next` may lead to any paragraph.
+    /// The unreachable paragraphs are not included.
+    fn entry_written(&self, loc: &Locale) -> HashMap<String, HashSet<String>> {
+        let graph = StoryGraph::new(self.game, loc);
+        let mut entry = HashMap::<String, HashSet<String>>::new();
+        let mut queue = VecDeque::new();
+        if let Some(start) = graph.start.as_deref().and_then(|tag| graph.node(tag)) {
+            entry.insert(start.tag.clone(), HashSet::new());
+            queue.push_back(start);
+        }
+        while let Some(node) = queue.pop_front() {
+            let mut exit = entry[&node.tag].clone();
+            exit.extend(
+                self.para_written
+                    .get(node.tag.as_str())
+                    .into_iter()
+                    .flatten()
+                    .cloned(),
+            );
+            let targets = graph
+                .nodes
+                .iter()
+                .filter(|n| node.next.dynamic || node.next.tags.contains(&n.tag));
+            for target in targets {
+                let (changed, written) = match entry.get_mut(&target.tag) {
+                    Some(written) => (!exit.is_subset(written), written),
+                    None => (true, entry.entry(target.tag.clone()).or_default()),
+                };
+                if changed {
+                    written.extend(exit.iter().cloned());
+                    queue.push_back(target);
+                }
+            }
+        }
+        entry
+    }
+
+    /// Checks all paragraphs in all locales.
+    pub fn check(&self) -> Vec<Diagnostic> {
+        let all_written = self
+            .para_written
+            .values()
+            .flatten()
+            .cloned()
+            .collect::<HashSet<_>>();
+        let mut res = vec![];
+        for loc in self.game.para_locales() {
+            let entry = self.entry_written(loc);
+            for para in self.game.paras.get(loc).into_iter().flatten() {
+                // The unreachable paragraphs are reported by the graph analysis.
+                let mut written = entry.get(&para.tag).unwrap_or(&all_written).clone();
+                for (index, text) in para_texts(para) {
+                    let mut kinds = vec![];
+                    self.check_text(loc, &text, &mut written, &mut kinds);
+                    if kinds.is_empty() {
+                        continue;
+                    }
+                    let pos = index.and_then(|index| {
+                        para.text_span(index)
+                            .map(|span| span.locate(&para.texts[index], 0))
+                    });
+                    res.extend(kinds.into_iter().map(|kind| Diagnostic {
+                        index,
+                        pos: pos.clone(),
+                        ..Diagnostic::new(loc, &para.tag, kind)
+                    }));
+                }
+            }
+        }
+        res
+    }
+
+    /// Checks a text, with the variables written before it,
+    /// and adds the variables written by it.
+    fn check_text(
+        &self,
+        loc: &Locale,
+        text: &Text,
+        written: &mut HashSet<String>,
+        res: &mut Vec<DiagnosticKind>,
+    ) {
+        for line in &text.0 {
+            match line {
+                Line::Cmd(Command::Other(name, args)) => {
+                    if !self.runtime.text_modules.contains_key(name) {
+                        res.push(DiagnosticKind::UnknownCommand(name.clone()));
+                    } else if let Some(&expected) = self.runtime.text_args.get(name) {
+                        if args.len() != expected {
+                            res.push(DiagnosticKind::ArgCount {
+                                name: name.clone(),
+                                expected,
+                                found: args.len(),
+                            });
+                        }
+                    }
+                }
+                Line::Cmd(Command::Unlock(id)) if self.game.find_unlock(id).is_none() => {
+                    res.push(DiagnosticKind::UnknownUnlock(id.clone()));
+                }
+                _ => {}
+            }
+        }
+        for p in text_programs(text) {
+            for e in &p.0 {
+                self.check_expr(loc, e, written, res);
+                collect_written(e, written);
+            }
+        }
+    }
+
+    fn check_expr(
+        &self,
+        loc: &Locale,
+        expr: &Expr,
+        written: &HashSet<String>,
+        res: &mut Vec<DiagnosticKind>,
+    ) {
+        match expr {
+            Expr::Ref(Ref::Res(key)) => {
+                let found = self
+                    .game
+                    .find_res_fallback(loc)
+                    .and_then(|map| map.get(key))
+                    .is_some();
+                if !found {
+                    res.push(DiagnosticKind::MissingResource(key.clone()));
+                }
+            }
+            Expr::Ref(Ref::Ctx(name)) => {
+                if !written.contains(name) {
+                    res.push(DiagnosticKind::UnwrittenVariable(name.clone()));
+                }
+            }
+            Expr::Ref(Ref::Var(_)) | Expr::Const(_) => {}
+            Expr::Unary(_, e) => self.check_expr(loc, e, written, res),
+            Expr::Binary(lhs, op, rhs) => {
+                // The assigned variable is not read.
+                if !matches!((op, lhs.as_ref()), (BinaryOp::Assign, Expr::Ref(_))) {
+                    self.check_expr(loc, lhs, written, res);
+                }
+                self.check_expr(loc, rhs, written, res);
+            }
+            Expr::Call(ns, name, args) => {
+                let found = if ns.is_empty() {
+                    INTRINSICS.contains(&name.as_str())
+                } else {
+                    self.runtime
+                        .modules
+                        .get(ns)
+                        .map(|m| m.has_method(name))
+                        .unwrap_or_default()
+                };
+                if !found {
+                    res.push(DiagnosticKind::UnknownFunction(ns.clone(), name.clone()));
+                }
+                for e in args {
+                    self.check_expr(loc, e, written, res);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{check::Checker, plugin::Runtime, *};
+    use std::{collections::HashMap, path::Path};
+
+    const GAME: &str = r#"
+title: Check
+base_lang: en
+res:
+  en:
+    name: Alice
+  ja:
+    title: タイトル
+unlocks:
+  - id: cg1
+paras:
+  en:
+    - tag: para1
+      texts:
+        - \ruby{a}\unknown{}\ruby{a}{b}\unlock{cg1}\unlock{cg2}
+        - '\exec{#name + #title}'
+        - \exec{$a = $b; $c += 1; foo.bar($c); baz(); if($a, 1, 2)}
+      next: \exec{if($d, "para2", "para3")}
+    - tag: para2
+      texts:
+        - \exec{$e}
+      next: para3
+    - tag: para3
+      on_enter: \exec{$e = 1}
+      texts:
+        - \exec{$e + $a}
+  ja:
+    - tag: para1
+      texts:
+        - '\exec{#name + #title}'
+"#;
+
+    #[test]
+    fn check() {
+        let game: Game = ConfigFormat::Yaml
+            .parse(GAME, Path::new("check.yaml"))
+            .unwrap();
+        let runtime = Runtime {
+            modules: HashMap::new(),
+            action_modules: vec![],
+            text_modules: HashMap::from([("ruby".into(), "basictex".into())]),
+            text_args: HashMap::from([("ruby".into(), 2)]),
+            game_modules: vec![],
+        };
+        let res = Checker::new(&game, &runtime)
+            .check()
+            .into_iter()
+            .map(|d| (d.locale.to_string(), d.index, d.kind))
+            .collect::<Vec<_>>();
+        use DiagnosticKind::*;
+        let en = || "en".to_string();
+        assert_eq!(
+            res,
+            [
+                (
+                    en(),
+                    Some(0),
+                    ArgCount {
+                        name: "ruby".into(),
+                        expected: 2,
+                        found: 1
+                    }
+                ),
+                (en(), Some(0), UnknownCommand("unknown".into())),
+                (en(), Some(0), UnknownUnlock("cg2".into())),
+                (en(), Some(1), MissingResource("title".into())),
+                (en(), Some(2), UnwrittenVariable("b".into())),
+                (en(), Some(2), UnwrittenVariable("c".into())),
+                (en(), Some(2), UnknownFunction("foo".into(), "bar".into())),
+                (en(), Some(2), UnknownFunction("".into(), "baz".into())),
+                (en(), None, UnwrittenVariable("d".into())),
+                // Only written after it.
+                (en(), Some(0), UnwrittenVariable("e".into())),
+            ]
+        );
+    }
+}
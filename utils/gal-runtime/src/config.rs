@@ -1,30 +1,94 @@
 pub use fallback::Fallback;
-pub use gal_bindings_types::{Action, Switch};
+pub use gal_bindings_types::{Action, Character, Switch};
 
 use crate::*;
-use serde::Deserialize;
-use std::{collections::HashMap, path::PathBuf};
+use anyhow::{bail, Context as _, Result};
+use gal_script::{Command, Line, TextParser};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
 
 /// The paragraph in a game config.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Paragraph {
     /// The tag and key of a paragraph.
     /// They are referenced in `next`.
     pub tag: String,
     /// The title of a paragraph.
     /// It can be [`None`], but better with a human-readable one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
     /// The texts.
     /// They will be parsed into [`gal_script::Text`] later.
     pub texts: Vec<String>,
     /// The next paragraph.
     /// If [`None`], the game meets the end.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub next: Option<String>,
+    /// Whether the paragraph is an ending.
+    /// A paragraph whose `next` may end the game is always an ending.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ending: bool,
+    /// The properties of the paragraph.
+    /// They are merged into every action of the paragraph,
+    /// and the ones set by the commands in the text take precedence.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub props: HashMap<String, PropValue>,
+    /// The script run when entering the paragraph, before the first text.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_enter: Option<String>,
+    /// The script run when exiting the paragraph, before `next` is evaluated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_exit: Option<String>,
+    /// The location in the source file, if it is YAML.
+    #[serde(skip)]
+    pub(crate) source: Option<ParaSource>,
+    #[serde(skip)]
+    pub(crate) text_keys: OnceLock<Vec<String>>,
+}
+
+/// The kind of an [`Unlock`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnlockKind {
+    /// An achievement.
+    #[default]
+    Achievement,
+    /// A CG in the gallery.
+    Cg,
+    /// A music in the music room.
+    Music,
+}
+
+/// An unlockable item, like an achievement, a CG or a music.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Unlock {
+    /// The id of the item.
+    /// It is referenced in `\unlock`.
+    pub id: String,
+    /// The kind of the item.
+    #[serde(default)]
+    pub kind: UnlockKind,
+    /// The titles, indexed by locale.
+    #[serde(default)]
+    pub titles: HashMap<Locale, String>,
+    /// The descriptions, indexed by locale.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub descriptions: HashMap<Locale, String>,
+    /// The path of the image or the music, relative to the config file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
 }
 
 /// The gal-game config.
-/// It should be deserialized from a YAML file.
-#[derive(Debug, Default, Deserialize)]
+/// It should be deserialized from a YAML, JSON or TOML file.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Game {
     /// The title of the game.
     pub title: String,
@@ -38,18 +102,57 @@ pub struct Game {
     pub plugins: PluginConfig,
     /// The global game properties.
     #[serde(default)]
-    pub props: HashMap<String, String>,
+    pub props: HashMap<String, PropValue>,
     /// The resources, indexed by locale.
     #[serde(default)]
     pub res: HashMap<Locale, VarMap>,
+    /// The declared characters, indexed by id.
+    /// The id is referenced in `\ch`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub characters: HashMap<String, Character>,
+    /// The unlockable items, in the order of display.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unlocks: Vec<Unlock>,
     /// The base language.
     /// If the runtime fails to choose a best match,
     /// it fallbacks to this one.
     pub base_lang: Locale,
+    /// The explicit fallback locales, before the base language.
+    /// If a locale is not specified here,
+    /// it fallbacks to the other locales by the matching distance.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub fallbacks: HashMap<Locale, Vec<Locale>>,
+    /// The included files, relative to this config file.
+    /// They are merged into this config in order.
+    /// Glob patterns are supported.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+}
+
+/// A part of the game config, in an included file.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct GamePart {
+    /// The paragraphs, indexed by locale.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub paras: HashMap<Locale, Vec<Paragraph>>,
+    /// The global game properties.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub props: HashMap<String, PropValue>,
+    /// The resources, indexed by locale.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub res: HashMap<Locale, VarMap>,
+    /// The declared characters, indexed by id.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub characters: HashMap<String, Character>,
+    /// The unlockable items, in the order of display.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unlocks: Vec<Unlock>,
 }
 
 /// The plugin config.
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct PluginConfig {
     /// The directory of the plugins.
     pub dir: PathBuf,
@@ -58,8 +161,376 @@ pub struct PluginConfig {
     pub modules: Vec<String>,
 }
 
+/// Gets the stable key of a text.
+///
+/// If the text specifies an id with `\id{}`, the id is the key;
+/// otherwise the key is a hash of the content, prefixed with `#`.
+pub fn text_key(text: &str) -> String {
+    if let Ok(t) = TextParser::new(text).parse() {
+        for line in t.0 {
+            if let Line::Cmd(Command::Id(id)) = line {
+                return id;
+            }
+        }
+    }
+    // FNV-1a, which is stable between builds.
+    let hash = text.trim().bytes().fold(0xcbf29ce484222325u64, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("#{:016x}", hash)
+}
+
+impl Paragraph {
+    /// Gets the stable keys of the texts, which are unique in the paragraph.
+    ///
+    /// A key occurring again is suffixed with the count of the previous occurrences,
+    /// like `#0123456789abcdef:1`.
+    /// The keys are computed on the first call, and cached.
+    pub fn text_keys(&self) -> &[String] {
+        self.text_keys.get_or_init(|| {
+            let mut counts = HashMap::<String, usize>::new();
+            self.texts
+                .iter()
+                .map(|t| {
+                    let key = text_key(t);
+                    let count = counts.entry(key.clone()).or_default();
+                    *count += 1;
+                    if *count > 1 {
+                        format!("{}:{}", key, *count - 1)
+                    } else {
+                        key
+                    }
+                })
+                .collect()
+        })
+    }
+
+    /// Gets the stable key of the text at `index`.
+    pub fn text_key(&self, index: usize) -> Option<&str> {
+        self.text_keys().get(index).map(|s| s.as_str())
+    }
+
+    /// Finds the index of the text with the stable key.
+    pub fn find_text_key(&self, key: &str) -> Option<usize> {
+        self.text_keys().iter().position(|k| k == key)
+    }
+
+    /// Gets the span of the text at `index` in the source file.
+    ///
+    /// It is [`None`] if the source is not YAML, or the text cannot be found.
+    pub fn text_span(&self, index: usize) -> Option<TextSpan> {
+        self.source
+            .as_ref()?
+            .text_span(index, self.texts.get(index)?)
+    }
+
+    /// Determines if the paragraph is an ending,
+    /// either marked with `ending`, or the game may end after it.
+    pub fn is_ending(&self) -> bool {
+        self.ending || NextTargets::new(self.next.as_deref()).end
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                prev
+            } else {
+                prev.min(cur).min(row[j]) + 1
+            };
+            prev = cur;
+        }
+    }
+    row[b.len()]
+}
+
+/// Suggests the most similar expected field for an unknown field error.
+fn suggest_field(msg: &str) -> Option<&str> {
+    let (_, rest) = msg.split_once("unknown field `")?;
+    let (field, expected) = rest.split_once('`')?;
+    expected
+        .split('`')
+        .skip(1)
+        .step_by(2)
+        .map(|name| (edit_distance(field, name), name))
+        .filter(|(dis, name)| *dis <= name.chars().count().max(3) / 3 + 1)
+        .min_by_key(|(dis, _)| *dis)
+        .map(|(_, name)| name)
+}
+
+/// The format of a config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    /// YAML, with extension `yaml` or `yml`.
+    Yaml,
+    /// JSON, with extension `json`.
+    Json,
+    /// TOML, with extension `toml`.
+    Toml,
+}
+
+impl ConfigFormat {
+    /// Chooses the format from the extension of the path.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        match path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("yaml" | "yml") => Ok(Self::Yaml),
+            Some("json") => Ok(Self::Json),
+            Some("toml") => Ok(Self::Toml),
+            _ => bail!("Unknown config format of {}", path.display()),
+        }
+    }
+
+    /// Deserializes the source.
+    /// The error message starts with the position in `path`, if available.
+    pub fn parse<T: DeserializeOwned>(self, source: &str, path: &Path) -> Result<T> {
+        let (msg, pos) = match self {
+            Self::Yaml => match serde_yaml::from_str(source) {
+                Ok(data) => return Ok(data),
+                Err(e) => (e.to_string(), e.location().map(|l| (l.line(), l.column()))),
+            },
+            Self::Json => match serde_json::from_str(source) {
+                Ok(data) => return Ok(data),
+                Err(e) => (
+                    e.to_string(),
+                    Some((e.line(), e.column())).filter(|p| p.0 > 0),
+                ),
+            },
+            Self::Toml => match toml::from_str(source) {
+                Ok(data) => return Ok(data),
+                Err(e) => (
+                    e.to_string(),
+                    e.line_col().map(|(line, col)| (line + 1, col + 1)),
+                ),
+            },
+        };
+        Err(config_error(path, msg, pos))
+    }
+
+    /// Serializes the data.
+    /// The map keys are sorted to make the output stable.
+    pub fn to_string<T: Serialize>(self, data: &T) -> Result<String> {
+        let value = serde_json::to_value(data)?;
+        let s = match self {
+            Self::Yaml => serde_yaml::to_string(&value)?,
+            Self::Json => serde_json::to_string_pretty(&value)? + "\n",
+            Self::Toml => toml::to_string(&toml::Value::try_from(value)?)?,
+        };
+        Ok(s)
+    }
+}
+
+fn config_error(path: &Path, msg: String, pos: Option<(usize, usize)>) -> anyhow::Error {
+    // The location is reported in front.
+    let mut msg = match (pos, msg.rfind(" at line ")) {
+        (Some((line, column)), Some(index)) => {
+            format!("{}:{}:{}: {}", path.display(), line, column, &msg[..index])
+        }
+        _ => format!("{}: {}", path.display(), msg),
+    };
+    if let Some(name) = suggest_field(&msg) {
+        msg.push_str(&format!("; did you mean `{}`?", name));
+    }
+    anyhow::Error::msg(msg)
+}
+
+/// Loads a config file, and returns the source if it is YAML.
+async fn load_config<T: DeserializeOwned>(path: &Path) -> Result<(T, Option<String>)> {
+    let format = ConfigFormat::from_path(path)?;
+    let source = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("Cannot read {}", path.display()))?;
+    let data = format.parse(&source, path)?;
+    Ok((data, (format == ConfigFormat::Yaml).then_some(source)))
+}
+
+/// Writes a config file.
+/// The format is chosen from the extension of the path.
+async fn write_config<T: Serialize>(path: &Path, data: &T) -> Result<()> {
+    let s = ConfigFormat::from_path(path)?.to_string(data)?;
+    tokio::fs::write(path, s)
+        .await
+        .with_context(|| format!("Cannot write {}", path.display()))?;
+    Ok(())
+}
+
+fn glob_files(root: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
+    let full = root.join(pattern);
+    let files = glob::glob(&full.to_string_lossy())
+        .with_context(|| format!("Invalid include pattern `{}`", pattern))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    if files.is_empty() {
+        bail!("The include pattern `{}` matches no file.", pattern);
+    }
+    Ok(files)
+}
+
+/// The source files of the merged items, to detect duplicates.
+#[derive(Default)]
+struct Sources {
+    paras: HashMap<(Locale, String), PathBuf>,
+    props: HashMap<String, PathBuf>,
+    res: HashMap<(Locale, String), PathBuf>,
+    characters: HashMap<String, PathBuf>,
+    unlocks: HashMap<String, PathBuf>,
+}
+
+impl GamePart {
+    /// Writes the part to a file.
+    /// The format is chosen from the extension of the path.
+    pub async fn write(&self, path: impl AsRef<Path>) -> Result<()> {
+        write_config(path.as_ref(), self).await
+    }
+}
+
+impl Sources {
+    fn add<K: Eq + std::hash::Hash>(
+        map: &mut HashMap<K, PathBuf>,
+        key: K,
+        file: &Path,
+        name: impl FnOnce() -> String,
+    ) -> Result<()> {
+        if let Some(prev) = map.insert(key, file.to_path_buf()) {
+            bail!(
+                "Duplicate {} in {}, which is already defined in {}.",
+                name(),
+                file.display(),
+                prev.display()
+            );
+        }
+        Ok(())
+    }
+
+    fn add_part(
+        &mut self,
+        paras: &HashMap<Locale, Vec<Paragraph>>,
+        props: &HashMap<String, PropValue>,
+        res: &HashMap<Locale, VarMap>,
+        characters: &HashMap<String, Character>,
+        unlocks: &[Unlock],
+        file: &Path,
+    ) -> Result<()> {
+        for (loc, paras) in paras {
+            for p in paras {
+                Self::add(&mut self.paras, (loc.clone(), p.tag.clone()), file, || {
+                    format!("paragraph `{}` of locale {}", p.tag, loc)
+                })?;
+            }
+        }
+        for key in props.keys() {
+            Self::add(&mut self.props, key.clone(), file, || {
+                format!("property `{}`", key)
+            })?;
+        }
+        for (loc, map) in res {
+            for key in map.keys() {
+                Self::add(&mut self.res, (loc.clone(), key.clone()), file, || {
+                    format!("resource `{}` of locale {}", key, loc)
+                })?;
+            }
+        }
+        for id in characters.keys() {
+            Self::add(&mut self.characters, id.clone(), file, || {
+                format!("character `{}`", id)
+            })?;
+        }
+        for unlock in unlocks {
+            Self::add(&mut self.unlocks, unlock.id.clone(), file, || {
+                format!("unlock `{}`", unlock.id)
+            })?;
+        }
+        Ok(())
+    }
+}
+
 impl Game {
-    fn choose_from_keys<'a, V>(&'a self, loc: &Locale, map: &'a HashMap<Locale, V>) -> &'a Locale {
+    /// Open a game config file, and merge the included files into it.
+    /// The format of each file is chosen from its extension.
+    ///
+    /// A paragraph tag, property, resource key, character id or unlock id can only be defined once
+    /// for each locale among all files.
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let (mut game, source): (Self, _) = load_config(path).await?;
+        if let Some(source) = source {
+            set_para_sources(&mut game.paras, source, path);
+        }
+        let mut sources = Sources::default();
+        sources.add_part(
+            &game.paras,
+            &game.props,
+            &game.res,
+            &game.characters,
+            &game.unlocks,
+            path,
+        )?;
+        let root = path.parent().unwrap_or_else(|| Path::new(""));
+        for pattern in game.include.clone() {
+            for file in glob_files(root, &pattern)? {
+                let (mut part, source): (GamePart, _) = load_config(&file).await?;
+                if let Some(source) = source {
+                    set_para_sources(&mut part.paras, source, &file);
+                }
+                sources.add_part(
+                    &part.paras,
+                    &part.props,
+                    &part.res,
+                    &part.characters,
+                    &part.unlocks,
+                    &file,
+                )?;
+                game.merge(part);
+            }
+        }
+        Ok(game)
+    }
+
+    /// Writes the game config to a file.
+    /// The format is chosen from the extension of the path.
+    pub async fn write(&self, path: impl AsRef<Path>) -> Result<()> {
+        write_config(path.as_ref(), self).await
+    }
+
+    /// Takes the paragraphs and resources of a locale out as a [`GamePart`],
+    /// which could be written to an included file.
+    pub fn take_locale(&mut self, loc: &Locale) -> GamePart {
+        GamePart {
+            paras: self.paras.remove_entry(loc).into_iter().collect(),
+            props: HashMap::new(),
+            res: self.res.remove_entry(loc).into_iter().collect(),
+            characters: HashMap::new(),
+            unlocks: vec![],
+        }
+    }
+
+    fn merge(&mut self, part: GamePart) {
+        for (loc, paras) in part.paras {
+            self.paras.entry(loc).or_default().extend(paras);
+        }
+        self.props.extend(part.props);
+        for (loc, map) in part.res {
+            self.res.entry(loc).or_default().extend(map);
+        }
+        self.characters.extend(part.characters);
+        self.unlocks.extend(part.unlocks);
+    }
+
+    pub(crate) fn choose_from_keys<'a, V>(
+        &'a self,
+        loc: &Locale,
+        map: &'a HashMap<Locale, V>,
+    ) -> &'a Locale {
         loc.choose_from(map.keys()).unwrap_or(&self.base_lang)
     }
 
@@ -74,35 +545,119 @@ impl Game {
         None
     }
 
+    /// Gets the fallback chain of the locale among the keys of the map.
+    ///
+    /// The chain starts with the best match if any, and ends with the base language.
+    /// The locales between are the explicit ones in [`Game::fallbacks`],
+    /// each matched among the keys, or the other matched ones sorted by distance.
+    pub fn fallback_chain<'a, V>(
+        &'a self,
+        loc: &Locale,
+        map: &'a HashMap<Locale, V>,
+    ) -> Vec<&'a Locale> {
+        let key = loc.choose_from(map.keys());
+        let fallbacks = self
+            .fallbacks
+            .get(loc)
+            .or_else(|| key.and_then(|key| self.fallbacks.get(key)));
+        let mut chain = match fallbacks {
+            Some(fallbacks) => key
+                .into_iter()
+                .chain(
+                    fallbacks
+                        .iter()
+                        .filter_map(|loc| loc.choose_from(map.keys())),
+                )
+                .collect(),
+            None => loc.sort_from(map.keys()),
+        };
+        chain.push(self.choose_from_keys(&self.base_lang, map));
+        let mut visited = HashSet::new();
+        chain.retain(|loc| visited.insert(*loc));
+        chain
+    }
+
     /// Find a paragraph by tag, with specified locale.
     pub fn find_para_fallback(&self, loc: &Locale, tag: &str) -> Fallback<&Paragraph> {
-        let key = self.choose_from_keys(loc, &self.paras);
-        let base_key = self.choose_from_keys(&self.base_lang, &self.paras);
-        Fallback::new(
-            if key == base_key {
-                None
-            } else {
-                self.find_para(key, tag)
-            },
-            self.find_para(base_key, tag),
+        Fallback::from_chain(
+            self.fallback_chain(loc, &self.paras)
+                .into_iter()
+                .map(|key| self.find_para(key, tag)),
         )
     }
 
+    /// Find a paragraph by tag, in the base language.
+    ///
+    /// The stable keys of the texts are calculated from this paragraph.
+    pub fn find_para_base(&self, tag: &str) -> Option<&Paragraph> {
+        self.find_para(self.choose_from_keys(&self.base_lang, &self.paras), tag)
+    }
+
+    /// The paragraphs in the base language.
+    pub fn paras_base(&self) -> &[Paragraph] {
+        self.paras
+            .get(self.choose_from_keys(&self.base_lang, &self.paras))
+            .map(|paras| paras.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// The ending paragraphs in the base language, in order.
+    pub fn endings(&self) -> impl Iterator<Item = &Paragraph> {
+        self.paras_base().iter().filter(|p| p.is_ending())
+    }
+
     fn find_res(&self, loc: &Locale) -> Option<&VarMap> {
         self.res.get(loc)
     }
 
     /// Find the resource map with specified locale.
     pub fn find_res_fallback(&self, loc: &Locale) -> Fallback<&VarMap> {
-        let key = self.choose_from_keys(loc, &self.res);
-        let base_key = self.choose_from_keys(&self.base_lang, &self.res);
-        Fallback::new(
-            if key == base_key {
-                None
-            } else {
-                self.find_res(key)
-            },
-            self.find_res(base_key),
+        Fallback::from_chain(
+            self.fallback_chain(loc, &self.res)
+                .into_iter()
+                .map(|key| self.find_res(key)),
         )
     }
+
+    /// Find an unlockable item by id.
+    pub fn find_unlock(&self, id: &str) -> Option<&Unlock> {
+        self.unlocks.iter().find(|unlock| unlock.id == id)
+    }
+
+    /// Find the localized text in a map indexed by locale,
+    /// like the titles of an [`Unlock`].
+    pub fn find_localized<'a>(
+        &'a self,
+        loc: &Locale,
+        map: &'a HashMap<Locale, String>,
+    ) -> Option<&'a String> {
+        self.fallback_chain(loc, map)
+            .into_iter()
+            .find_map(|loc| map.get(loc))
+    }
+
+    /// Find the display name of a character with specified locale.
+    ///
+    /// The names of the declared character are preferred,
+    /// and the resource `ch_{key}` is used if the character is not declared.
+    pub fn find_character_name(&self, loc: &Locale, key: &str) -> Option<String> {
+        if let Some(names) = self.characters.get(key).map(|ch| {
+            ch.names
+                .iter()
+                .filter_map(|(loc, name)| loc.parse::<Locale>().ok().map(|loc| (loc, name)))
+                .collect::<HashMap<_, _>>()
+        }) {
+            if let Some(name) = self
+                .fallback_chain(loc, &names)
+                .into_iter()
+                .find_map(|loc| names.get(loc))
+            {
+                return Some((*name).clone());
+            }
+        }
+        let res_key = format!("ch_{}", key);
+        self.find_res_fallback(loc)
+            .and_then(|map| map.get(&res_key))
+            .map(|v| v.get_str().into_owned())
+    }
 }
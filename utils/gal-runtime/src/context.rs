@@ -1,6 +1,8 @@
 pub use gal_bindings_types::{FrontendType, RawContext};
 
 use crate::{
+    check::Checker,
+    event::Subscriber,
     plugin::{LoadStatus, Runtime},
     *,
 };
@@ -8,12 +10,14 @@ use anyhow::{anyhow, bail, Result};
 use gal_bindings_types::{
     ActionLine, ActionLines, ActionProcessContextRef, GameProcessContextRef, TextProcessContextRef,
 };
-use gal_script::{Command, Line, Loc, ParseError, Program, Text, TextParser};
-use log::error;
+use gal_script::{Command, Line, Loc, ParseError, Text, TextParser};
+use log::{error, warn};
 use script::*;
+use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
 };
 use unicode_width::UnicodeWidthStr;
 
@@ -25,11 +29,77 @@ pub struct Context {
     root_path: PathBuf,
     runtime: Runtime,
     settings: Settings,
+    locale: Locale,
     global_record: GlobalRecord,
     /// The inner raw context.
     pub ctx: RawContext,
     /// The inner record.
     pub record: ActionRecord,
+    storage: Option<Box<dyn Storage>>,
+    auto_save_pending: bool,
+    actions_since_save: usize,
+    enter_pending: bool,
+    subscribers: Vec<Subscriber>,
+}
+
+/// A saved context which cannot be resolved against the current [`Game`].
+#[derive(Debug, Clone)]
+pub struct UnresolvedContext {
+    /// The index in the history of the record.
+    pub index: usize,
+    /// The saved context.
+    pub ctx: RawContext,
+}
+
+/// The information of an unlockable item, with the localized texts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnlockInfo {
+    /// The id of the item.
+    pub id: String,
+    /// The kind of the item.
+    pub kind: UnlockKind,
+    /// The localized title.
+    pub title: String,
+    /// The localized description.
+    pub description: String,
+    /// The full path of the image or the music.
+    pub path: Option<PathBuf>,
+    /// The unlock time, in seconds since the UNIX epoch.
+    /// It is [`None`] if the item is still locked.
+    pub unlocked: Option<u64>,
+}
+
+/// The information of an ending, with the localized title.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EndingInfo {
+    /// The tag of the ending paragraph.
+    pub tag: String,
+    /// The localized title of the paragraph.
+    pub title: Option<String>,
+    /// The first reach time, in seconds since the UNIX epoch.
+    /// It is [`None`] if the ending hasn't been reached.
+    pub reached: Option<u64>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Collects the variables in `prev` which are changed in `locals`,
+/// and the ones not existing in `prev` as [`RawValue::Unit`].
+fn locals_diff(prev: &VarMap, locals: &VarMap) -> VarMap {
+    let mut diff = prev
+        .iter()
+        .filter(|&(key, value)| locals.get(key) != Some(value))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect::<VarMap>();
+    for key in locals.keys().filter(|key| !prev.contains_key(*key)) {
+        diff.insert(key.clone(), RawValue::Unit);
+    }
+    diff
 }
 
 /// The open status when creating [`Context`].
@@ -48,8 +118,7 @@ impl Context {
     #[stream(OpenStatus, lifetime = "'a")]
     pub async fn open<'a>(path: impl AsRef<Path> + 'a, frontend: FrontendType) -> Result<Self> {
         yield OpenStatus::LoadProfile;
-        let file = tokio::fs::read(&path).await?;
-        let mut game: Game = serde_yaml::from_slice(&file)?;
+        let mut game = Game::open(&path).await?;
         let root_path = path
             .as_ref()
             .parent()
@@ -75,13 +144,15 @@ impl Context {
                 author: &game.author,
                 root_path: &root_path,
                 props: &game.props,
+                characters: &game.characters,
             };
             let res = module.process_game(ctx)?;
             for (key, value) in res.props {
                 game.props.insert(key, value);
             }
         }
-        Ok(Self {
+        let mut ctx = Self {
+            locale: game.base_lang.clone(),
             game,
             frontend,
             root_path,
@@ -90,30 +161,100 @@ impl Context {
             global_record: GlobalRecord::default(),
             ctx: RawContext::default(),
             record: ActionRecord::default(),
-        })
+            storage: None,
+            auto_save_pending: false,
+            actions_since_save: 0,
+            enter_pending: false,
+            subscribers: vec![],
+        };
+        ctx.update_locale();
+        Ok(ctx)
     }
 
     /// Initialize the [`RawContext`] to the start of the game.
     pub fn init_new(&mut self) {
-        self.init_context(ActionRecord { history: vec![] })
+        self.init_context(ActionRecord { history: vec![] });
     }
 
     /// Initialize the [`ActionRecord`] with given record.
-    pub fn init_context(&mut self, record: ActionRecord) {
+    ///
+    /// The saved positions are resolved against the current [`Game`] by the stable keys,
+    /// and the ones cannot be resolved are returned.
+    pub fn init_context(&mut self, mut record: ActionRecord) -> Vec<UnresolvedContext> {
+        let unresolved = record
+            .history
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, act)| {
+                if self.resolve_context(&mut act.ctx) {
+                    None
+                } else {
+                    Some(UnresolvedContext {
+                        index,
+                        ctx: act.ctx.clone(),
+                    })
+                }
+            })
+            .collect();
         self.ctx = record.last_ctx_with_game(&self.game);
         self.record = record;
+        self.auto_save_pending = false;
+        self.actions_since_save = 0;
+        // A new game enters the first paragraph.
+        self.enter_pending = self.record.history.is_empty();
         if !self.record.history.is_empty() {
             // If the record is not empty,
             // we need to set current context to the next one.
             self.ctx.cur_act += 1;
+            self.ctx.act_key = None;
+            self.ctx.turns += 1;
+        }
+        unresolved
+    }
+
+    fn resolve_context(&self, ctx: &mut RawContext) -> bool {
+        // The paragraph is found in the fallback chain, as it is run.
+        if !self
+            .game
+            .find_para_fallback(self.locale(), &ctx.cur_para)
+            .is_some()
+        {
+            return false;
+        }
+        let para = match self.game.find_para_base(&ctx.cur_para) {
+            Some(para) => para,
+            // No keys to resolve with.
+            None => return true,
+        };
+        let key = match &ctx.act_key {
+            Some(key) => key,
+            // Saved before the keys were introduced.
+            None => return true,
+        };
+        if para.text_key(ctx.cur_act) == Some(key.as_str()) {
+            true
+        } else if let Some(index) = para.find_text_key(key) {
+            log::debug!(
+                "Resolve para {}, act {} to act {}",
+                ctx.cur_para,
+                ctx.cur_act,
+                index
+            );
+            ctx.cur_act = index;
+            true
+        } else {
+            false
         }
     }
 
     fn table(&mut self) -> VarTable {
         VarTable::new(
             &self.runtime,
+            &self.game,
+            self.locale().clone(),
             self.game.find_res_fallback(self.locale()),
-            &mut self.ctx.locals,
+            &mut self.ctx,
+            &self.global_record,
         )
     }
 
@@ -138,17 +279,38 @@ impl Context {
 
     /// Set the current locale.
     pub fn set_locale(&mut self, loc: impl Into<Locale>) {
-        self.settings.lang = loc.into();
+        self.settings.lang = loc.into().into();
+        self.update_locale();
+    }
+
+    /// Set the preferred locales, the most preferred first.
+    pub fn set_locale_preferences(&mut self, prefs: LocalePreferences) {
+        self.settings.lang = prefs;
+        self.update_locale();
     }
 
-    /// Get the current locale.
+    /// Get the current locale, negotiated from the preferred locales
+    /// among the locales of the paragraphs and the resources.
+    ///
+    /// It is the base language if none of the preferred locales matches.
     pub fn locale(&self) -> &Locale {
-        &self.settings.lang
+        &self.locale
+    }
+
+    fn update_locale(&mut self) {
+        self.locale = self
+            .settings
+            .lang
+            .choose_from(self.game.paras.keys().chain(self.game.res.keys()))
+            .map(|(loc, _)| loc)
+            .unwrap_or(&self.game.base_lang)
+            .clone();
     }
 
     /// Set all settings.
     pub fn set_settings(&mut self, s: Settings) {
         self.settings = s;
+        self.update_locale();
     }
 
     /// Get all settings.
@@ -157,7 +319,8 @@ impl Context {
     }
 
     /// Set global record.
-    pub fn set_global_record(&mut self, r: GlobalRecord) {
+    pub fn set_global_record(&mut self, mut r: GlobalRecord) {
+        r.upgrade_read(&self.game);
         self.global_record = r;
     }
 
@@ -166,23 +329,215 @@ impl Context {
         &self.global_record
     }
 
+    /// Unlocks an item by id, and records the time in the [`GlobalRecord`].
+    ///
+    /// Returns `true` if the item is newly unlocked.
+    /// An item unlocked before keeps the first unlock time.
+    pub fn unlock(&mut self, id: &str) -> bool {
+        if self.game.find_unlock(id).is_none() {
+            warn!("Cannot find unlock `{}`.", id);
+            return false;
+        }
+        if self.global_record.unlocks.contains_key(id) {
+            false
+        } else {
+            self.global_record
+                .unlocks
+                .insert(id.to_string(), now_secs());
+            true
+        }
+    }
+
+    /// Lists the unlockable items with the texts in the current locale.
+    pub fn unlocks(&self) -> Vec<UnlockInfo> {
+        let loc = self.locale();
+        self.game
+            .unlocks
+            .iter()
+            .map(|unlock| UnlockInfo {
+                id: unlock.id.clone(),
+                kind: unlock.kind,
+                title: self
+                    .game
+                    .find_localized(loc, &unlock.titles)
+                    .cloned()
+                    .unwrap_or_else(|| unlock.id.clone()),
+                description: self
+                    .game
+                    .find_localized(loc, &unlock.descriptions)
+                    .cloned()
+                    .unwrap_or_default(),
+                path: unlock.path.as_ref().map(|path| self.root_path.join(path)),
+                unlocked: self.global_record.unlocks.get(&unlock.id).copied(),
+            })
+            .collect()
+    }
+
+    /// Lists the endings with the titles in the current locale.
+    pub fn endings(&self) -> Vec<EndingInfo> {
+        self.game
+            .endings()
+            .map(|p| EndingInfo {
+                tag: p.tag.clone(),
+                title: self
+                    .game
+                    .find_para_fallback(self.locale(), &p.tag)
+                    .and_then(|p| p.title.clone()),
+                reached: self.global_record.endings.get(&p.tag).copied(),
+            })
+            .collect()
+    }
+
+    /// The percentage of the read texts in the base language, from 0 to 100.
+    ///
+    /// It is the number of the read text keys in the [`GlobalRecord`],
+    /// divided by the number of the text keys of all paragraphs.
+    pub fn completion(&self) -> f64 {
+        let (read, total) = self
+            .game
+            .paras_base()
+            .iter()
+            .map(|p| {
+                let keys = p.text_keys();
+                let read = keys
+                    .iter()
+                    .filter(|key| self.global_record.is_read(&p.tag, key))
+                    .count();
+                (read, keys.len())
+            })
+            .fold((0, 0), |(r, t), (read, total)| (r + read, t + total));
+        if total == 0 {
+            100.0
+        } else {
+            read as f64 * 100.0 / total as f64
+        }
+    }
+
+    /// Subscribes the [`ContextEvent`]s emitted when running the game.
+    pub fn subscribe(&mut self, f: impl FnMut(&ContextEvent) + Send + Sync + 'static) {
+        self.subscribers.push(Box::new(f));
+    }
+
+    fn emit(&mut self, event: ContextEvent) {
+        for f in &mut self.subscribers {
+            f(&event);
+        }
+    }
+
+    /// Emits the changed variables, compared with the old ones.
+    fn emit_locals(&mut self, old: &VarMap) {
+        let mut changes = self
+            .ctx
+            .locals
+            .iter()
+            .filter(|(name, value)| old.get(*name) != Some(*value))
+            .map(|(name, value)| (name.clone(), Some(value.clone())))
+            .chain(
+                old.keys()
+                    .filter(|name| !self.ctx.locals.contains_key(*name))
+                    .map(|name| (name.clone(), None)),
+            )
+            .collect::<Vec<_>>();
+        changes.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (name, value) in changes {
+            self.emit(ContextEvent::VariableChanged { name, value });
+        }
+    }
+
+    fn emit_action(&mut self, action: &Action) {
+        self.emit(ContextEvent::Action {
+            action: Box::new(action.clone()),
+            visited: self.visited(action),
+        });
+        if !action.switches.is_empty() {
+            self.emit(ContextEvent::SwitchPresented(action.switches.clone()));
+        }
+    }
+
+    /// Set the storage of the save slots.
+    pub fn set_storage(&mut self, storage: impl Storage + 'static) {
+        self.storage = Some(Box::new(storage));
+    }
+
+    fn storage(&self) -> Result<&dyn Storage> {
+        self.storage
+            .as_deref()
+            .ok_or_else(|| anyhow!("The storage is not set."))
+    }
+
+    /// Get the record compacted with the policy in [`Settings`].
+    pub fn compacted_record(&self) -> ActionRecord {
+        let mut record = self.record.clone();
+        record.compact(&self.settings.history);
+        record
+    }
+
+    /// Save the current record into the slot.
+    pub async fn save_slot(&self, slot: SaveSlot) -> Result<()> {
+        save_slot_record(
+            self.storage()?,
+            &self.game.title,
+            slot,
+            &self.compacted_record(),
+            SaveFormat::Binary,
+        )
+        .await
+    }
+
+    /// Load the record from the slot, and initialize the context with it.
+    pub async fn load_slot(&mut self, slot: SaveSlot) -> Result<Vec<UnresolvedContext>> {
+        let record = load_slot_record(self.storage()?, &self.game.title, slot).await?;
+        Ok(self.init_context(record))
+    }
+
+    /// Write the auto save, if the policy in [`Settings`] requires
+    /// since the last call.
+    /// It should be called after [`Context::next_run`].
+    ///
+    /// Returns whether the auto save is written.
+    pub async fn auto_save(&mut self) -> Result<bool> {
+        if !self.auto_save_pending {
+            return Ok(false);
+        }
+        self.save_slot(SaveSlot::Auto).await?;
+        self.auto_save_pending = false;
+        self.actions_since_save = 0;
+        Ok(true)
+    }
+
     /// Determine if an [`Action`] has been visited,
     /// by the paragraph tag and action index.
     pub fn visited(&self, action: &Action) -> bool {
-        if let Some(max_act) = self.global_record.record.get(&action.ctx.cur_para) {
-            log::debug!("Test act: {}, max act: {}", action.ctx.cur_act, max_act);
-            *max_act >= action.ctx.cur_act
-        } else {
-            false
-        }
+        self.action_key(&action.ctx)
+            .map(|key| self.global_record.is_read(&action.ctx.cur_para, &key))
+            .unwrap_or_default()
+    }
+
+    /// Gets the stable key of the text of the context,
+    /// from the game if not saved in the context.
+    fn action_key(&self, ctx: &RawContext) -> Option<String> {
+        ctx.act_key.clone().or_else(|| {
+            self.game
+                .find_para_base(&ctx.cur_para)
+                .and_then(|p| p.text_key(ctx.cur_act))
+                .map(|key| key.to_string())
+        })
     }
 
     /// Call the part of script with this context.
+    ///
+    /// The changed variables are emitted to the subscribers.
     pub fn call(&mut self, expr: &impl Callable) -> RawValue {
-        self.table().call(expr)
+        if self.subscribers.is_empty() {
+            return self.table().call(expr);
+        }
+        let locals = self.ctx.locals.clone();
+        let res = self.table().call(expr);
+        self.emit_locals(&locals);
+        res
     }
 
-    fn rich_error(&self, text: &str, e: &ParseError) -> String {
+    fn rich_error(&self, text: &str, e: &ParseError, span: Option<&TextSpan>) -> String {
         use std::iter::repeat;
         const FREE_LEN: usize = 20;
 
@@ -203,8 +558,11 @@ impl Context {
         let show_code = &text[pre..post];
         let pre_code = &text[pre..loc.0];
         let error_code = &text[loc.0..loc.1];
+        let pos = span
+            .map(|span| format!("{}: ", span.locate(text, loc.0)))
+            .unwrap_or_default();
         format!(
-            "Parse error on paragraph \"{para_name}\", act {act_num}:\n    {show_code}\n    {}\n{e}\n",
+            "{pos}Parse error on paragraph \"{para_name}\", act {act_num}:\n    {show_code}\n    {}\n{e}\n",
             repeat(' ')
                 .take(UnicodeWidthStr::width_cjk(pre_code))
                 .chain(repeat('^').take(UnicodeWidthStr::width_cjk(error_code)))
@@ -228,17 +586,17 @@ impl Context {
                         // TODO: reduce allocation
                         chkey = Some(key.clone());
                         chname = if alter.is_empty() {
-                            let res_key = format!("ch_{}", key);
-                            self.game
-                                .find_res_fallback(self.locale())
-                                .and_then(|map| map.get(&res_key))
-                                .map(|v| v.get_str().into_owned())
+                            self.game.find_character_name(self.locale(), &key)
                         } else {
                             Some(alter)
                         }
                     }
+                    Command::Id(_) => {}
+                    Command::Unlock(id) => {
+                        self.unlock(&id);
+                    }
                     Command::Exec(p) => {
-                        let param = self.call(&p);
+                        let param = self.table().call(&p);
                         action_line.push_back_chars(format!("{{{}}}", action_line_params.len()));
                         action_line_params.push(param);
                     }
@@ -248,7 +606,9 @@ impl Context {
                         enabled,
                     } => {
                         // unwrap: when enabled is None, it means true.
-                        let enabled = enabled.map(|p| self.call(&p).get_bool()).unwrap_or(true);
+                        let enabled = enabled
+                            .map(|p| self.table().call(&p).get_bool())
+                            .unwrap_or(true);
                         switches.push(Switch { text, enabled });
                         switch_actions.push(action);
                     }
@@ -285,59 +645,11 @@ impl Context {
             switches,
             props,
             switch_actions,
+            prev_locals: None,
         })
     }
 
-    fn merge_action(&self, actions: Fallback<Action>) -> Option<Action> {
-        if actions.is_some() {
-            let actions = actions.spec();
-
-            let ctx = actions.ctx.fallback().unwrap_or_default();
-            let line = actions.line.and_any().unwrap_or_default();
-            let line_params = actions.line_params.and_any().unwrap_or_default();
-            let ch_key = actions.ch_key.flatten().and_any();
-            let character = actions.character.flatten().and_any();
-            let para_title = actions.para_title.flatten().and_any();
-            let switches = actions
-                .switches
-                .into_iter()
-                .map(|s| {
-                    let s = s.spec();
-                    let text = s.text.and_any().unwrap_or_default();
-                    let (enabled, base_enabled) = s.enabled.unzip();
-                    let enabled = base_enabled.or(enabled).unwrap_or(true);
-                    Switch { text, enabled }
-                })
-                .collect();
-            let (props, base_props) = actions.props.unzip();
-            let (mut props, base_props) =
-                (props.unwrap_or_default(), base_props.unwrap_or_default());
-            for (key, value) in base_props.into_iter() {
-                props.entry(key).or_insert(value);
-            }
-            let switch_actions = actions
-                .switch_actions
-                .into_iter()
-                .map(|act| act.map(|p| p.0).and_any().map(Program))
-                .map(|p| p.unwrap_or_default())
-                .collect();
-            Some(Action {
-                ctx,
-                line,
-                line_params,
-                ch_key,
-                character,
-                para_title,
-                switches,
-                props,
-                switch_actions,
-            })
-        } else {
-            None
-        }
-    }
-
-    fn process_action(&mut self, mut action: Action) -> Result<Action> {
+    fn process_action(&self, mut action: Action, last_action: Option<&Action>) -> Result<Action> {
         {
             let params = std::mem::take(&mut action.line_params);
             let named = HashMap::<String, RawValue>::new();
@@ -351,13 +663,13 @@ impl Context {
                 }
             }
         }
-        let last_action = self.record.history.last();
         for action_module in &self.runtime.action_modules {
             let module = &self.runtime.modules[action_module];
             let ctx = ActionProcessContextRef {
                 root_path: &self.root_path,
                 game_props: &self.game.props,
                 frontend: self.frontend,
+                characters: &self.game.characters,
                 last_action,
                 action: &action,
             };
@@ -377,25 +689,162 @@ impl Context {
                 break;
             }
         }
+        Ok(action)
+    }
+
+    fn push_action(&mut self, action: &Action) {
         if !action.line.is_empty() || action.character.is_some() {
             self.record.history.push(action.clone());
+            self.actions_since_save += 1;
+            let interval = self.settings.auto_save.interval;
+            if interval > 0 && self.actions_since_save >= interval {
+                self.auto_save_pending = true;
+            }
+        }
+    }
+
+    /// Exacts the current text in the locales of the fallback chain, and merges them.
+    fn exact_current_text(&mut self) -> Option<Action> {
+        let cur_text = self.current_text();
+        if cur_text.is_some() {
+            let prev_locals = self.ctx.locals.clone();
+            let text = cur_text.map(|act| self.parse_text_rich_error(act));
+            let para_title = self
+                .current_paragraph()
+                .and_then(|p| p.title.as_ref())
+                .cloned();
+            let actions = text.map(|t| {
+                self.exact_text(para_title.clone(), t).unwrap_or_else(|e| {
+                    error!("Exact text error: {}", e);
+                    Action::default()
+                })
+            });
+            actions.merge().map(|mut act| {
+                let props = self.current_paragraph().map(|p| p.props.clone()).extend();
+                for (key, value) in props {
+                    act.props.entry(key).or_insert(value);
+                }
+                act.prev_locals = Some(locals_diff(&prev_locals, &act.ctx.locals));
+                act
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Runs the script of the current paragraph, like `on_enter` and `on_exit`.
+    fn call_para_script(&mut self, f: impl Fn(&Paragraph) -> Option<&String>) {
+        let text = self
+            .current_paragraph()
+            .and_then(f)
+            .map(|script| self.parse_text_rich_error(script));
+        if let Some(text) = text {
+            self.table().call(&text);
         }
-        Ok(action)
+    }
+
+    /// Re-renders the text of the action.
+    ///
+    /// The scripts are evaluated with the variables before the action,
+    /// and the context is restored after rendering, so the side effects are discarded.
+    /// The action at `prev` in the history is passed to the action plugins as the last action.
+    ///
+    /// Returns [`None`] if the action doesn't store the variables before it.
+    fn rerender_impl(&mut self, action: &Action, prev: Option<usize>) -> Option<Action> {
+        let mut locals = action.ctx.locals.clone();
+        for (key, value) in action.prev_locals.clone()? {
+            if value == RawValue::Unit {
+                locals.remove(&key);
+            } else {
+                locals.insert(key, value);
+            }
+        }
+        let saved = std::mem::replace(
+            &mut self.ctx,
+            RawContext {
+                locals,
+                ..action.ctx.clone()
+            },
+        );
+        let res = self.exact_current_text();
+        self.ctx = saved;
+        let mut res = res?;
+        res.ctx = action.ctx.clone();
+        let last_action = prev.map(|prev| &self.record.history[prev]);
+        self.process_action(res, last_action)
+            .map_err(|e| error!("Error when processing action: {}", e))
+            .ok()
+    }
+
+    /// Re-renders an action in the current locale.
+    ///
+    /// The scripts are evaluated with a copy of the variables before the action,
+    /// so the side effects are not applied again.
+    /// Returns [`None`] if the text of the saved context cannot be found,
+    /// or the action is saved by an old version without the variables before it.
+    pub fn rerender(&mut self, action: &Action) -> Option<Action> {
+        let prev = match self.record.history.iter().rposition(|act| {
+            act.ctx.cur_para == action.ctx.cur_para && act.ctx.cur_act == action.ctx.cur_act
+        }) {
+            Some(index) => index.checked_sub(1),
+            // The action is not pushed into the history.
+            None => self.record.history.len().checked_sub(1),
+        };
+        self.rerender_impl(action, prev)
+    }
+
+    /// Switch the locale in the middle of the game.
+    ///
+    /// The position is kept, and the last action in the history is re-rendered in the new locale.
+    /// If `backlog` is true, all actions in the history are re-rendered.
+    ///
+    /// Returns the last action in the history.
+    pub fn switch_locale(
+        &mut self,
+        prefs: impl Into<LocalePreferences>,
+        backlog: bool,
+    ) -> Option<Action> {
+        self.settings.lang = prefs.into();
+        self.update_locale();
+        let len = self.record.history.len();
+        let start = if backlog { 0 } else { len.saturating_sub(1) };
+        for index in start..len {
+            let action = self.record.history[index].clone();
+            if let Some(action) = self.rerender_impl(&action, index.checked_sub(1)) {
+                self.record.history[index] = action;
+            }
+        }
+        self.record.history.last().cloned()
+    }
+
+    fn current_text_span(&self, text: &str) -> Option<TextSpan> {
+        let index = self.ctx.cur_act;
+        self.current_paragraph().and_then(|p| {
+            if p.texts.get(index).map(|s| s.as_str()) == Some(text) {
+                p.text_span(index)
+            } else {
+                None
+            }
+        })
     }
 
     fn parse_text_rich_error(&self, text: &str) -> Text {
         match TextParser::new(text).parse() {
             Ok(t) => t,
             Err(e) => {
-                error!("{}", self.rich_error(text, &e));
+                error!(
+                    "{}",
+                    self.rich_error(text, &e, self.current_text_span(text).as_ref())
+                );
                 Text::default()
             }
         }
     }
 
-    fn check_text_rich_error(&self, text: &str) -> bool {
+    fn check_text_rich_error(&self, text: &str, span: impl FnOnce() -> Option<TextSpan>) -> bool {
         if let Err(e) = TextParser::new(text).parse() {
-            eprintln!("{}", self.rich_error(text, &e));
+            // The span is only located when needed.
+            eprintln!("{}", self.rich_error(text, &e, span().as_ref()));
             false
         } else {
             true
@@ -404,41 +853,102 @@ impl Context {
 
     /// Step to next line.
     pub fn next_run(&mut self) -> Option<Action> {
+        if self.subscribers.is_empty() {
+            return self.next_run_impl();
+        }
+        let locals = self.ctx.locals.clone();
+        let action = self.next_run_impl();
+        self.emit_locals(&locals);
+        if let Some(action) = &action {
+            self.emit_action(action);
+        }
+        action
+    }
+
+    fn next_run_impl(&mut self) -> Option<Action> {
         if let Some(action) = self.record.history.last() {
-            self.global_record
-                .record
-                .entry(action.ctx.cur_para.clone())
-                .and_modify(|act| *act = (*act).max(action.ctx.cur_act))
-                .or_insert(action.ctx.cur_act);
+            if let Some(key) = self.action_key(&action.ctx) {
+                self.global_record.mark_read(&action.ctx.cur_para, &key);
+            }
+            if self.settings.auto_save.on_switch && !action.switches.is_empty() {
+                self.auto_save_pending = true;
+            }
+        }
+        if self.enter_pending {
+            self.enter_pending = false;
+            if !self.subscribers.is_empty() && self.current_paragraph().is_some() {
+                let title = self.current_paragraph().and_then(|p| p.title.clone());
+                self.emit(ContextEvent::ParagraphChanged {
+                    tag: self.ctx.cur_para.clone(),
+                    title,
+                });
+            }
+            *self
+                .ctx
+                .visits
+                .entry(self.ctx.cur_para.clone())
+                .or_default() += 1;
+            self.call_para_script(|p| p.on_enter.as_ref());
         }
+        self.ctx.act_key = self
+            .game
+            .find_para_base(&self.ctx.cur_para)
+            .and_then(|p| p.text_key(self.ctx.cur_act))
+            .map(|key| key.to_string());
         let cur_para = self.current_paragraph();
         if cur_para.is_some() {
-            let cur_text = self.current_text();
-            if cur_text.is_some() {
-                let text = cur_text.map(|act| self.parse_text_rich_error(act));
-                let para_title = cur_para.and_then(|p| p.title.as_ref()).cloned();
-                let actions = text.map(|t| {
-                    self.exact_text(para_title.clone(), t).unwrap_or_else(|e| {
-                        error!("Exact text error: {}", e);
-                        Action::default()
-                    })
-                });
-                let res = self.merge_action(actions).map(|act| {
-                    self.process_action(act).unwrap_or_else(|e| {
-                        error!("Error when processing action: {}", e);
-                        Action::default()
-                    })
+            if self.current_text().is_some() {
+                let res = self.exact_current_text().map(|act| {
+                    let act = self
+                        .process_action(act, self.record.history.last())
+                        .unwrap_or_else(|e| {
+                            error!("Error when processing action: {}", e);
+                            Action::default()
+                        });
+                    self.push_action(&act);
+                    act
                 });
                 self.ctx.cur_act += 1;
+                self.ctx.act_key = None;
+                self.ctx.turns += 1;
                 res
             } else {
-                self.ctx.cur_para = cur_para
+                let ending = self
+                    .game
+                    .find_para_base(&self.ctx.cur_para)
+                    .map(|p| p.ending)
+                    .unwrap_or_default();
+                self.call_para_script(|p| p.on_exit.as_ref());
+                let next = self
+                    .current_paragraph()
                     .and_then(|p| p.next.as_ref())
                     .map(|next| self.parse_text_rich_error(next))
-                    .map(|text| self.call(&text).into_str())
+                    .map(|text| self.table().call(&text).into_str())
                     .unwrap_or_default();
+                let last_para = std::mem::replace(&mut self.ctx.cur_para, next);
+                let end = !self.current_paragraph().is_some();
+                if end && !self.ctx.cur_para.is_empty() {
+                    warn!(
+                        "Cannot find the next paragraph `{}` of `{}`.",
+                        self.ctx.cur_para, last_para
+                    );
+                }
+                // A missing next paragraph is a config error rather than an ending.
+                if ending || self.ctx.cur_para.is_empty() {
+                    self.global_record
+                        .endings
+                        .entry(last_para.clone())
+                        .or_insert_with(now_secs);
+                }
+                if end {
+                    self.emit(ContextEvent::GameEnded(last_para));
+                }
                 self.ctx.cur_act = 0;
-                self.next_run()
+                self.enter_pending = true;
+                if self.settings.auto_save.on_paragraph {
+                    self.auto_save_pending = true;
+                }
+                self.next_run_impl()
             }
         } else {
             None
@@ -448,17 +958,35 @@ impl Context {
     /// Step back to the last run.
     pub fn next_back_run(&mut self) -> Option<Action> {
         if let Some(last_action) = self.record.history.pop() {
-            self.ctx = last_action.ctx;
+            let locals = std::mem::replace(&mut self.ctx, last_action.ctx).locals;
+            self.enter_pending = false;
             log::debug!(
                 "Back to para {}, act {}",
                 self.ctx.cur_para,
                 self.ctx.cur_act
             );
+            if !self.subscribers.is_empty() {
+                self.emit_locals(&locals);
+                if let Some(action) = self.record.history.last().cloned() {
+                    self.emit_action(&action);
+                }
+            }
         }
         self.record.history.last().cloned()
     }
 
-    /// Check all paragraphs to find grammer errors.
+    /// Analyzes the paragraph graph, and checks the commands,
+    /// resources, functions and variables in the texts.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut res = self.game.analyze();
+        res.extend(Checker::new(&self.game, &self.runtime).check());
+        res
+    }
+
+    /// Check all paragraphs to find grammer errors,
+    /// and report the [`Context::diagnostics`].
+    ///
+    /// The diagnostics are printed, and only errors fail the check.
     pub fn check(&mut self) -> bool {
         let mut succeed = true;
         for paras in self.game.paras.values() {
@@ -466,13 +994,17 @@ impl Context {
                 self.ctx.cur_para = para.tag.clone();
                 for (index, act) in para.texts.iter().enumerate() {
                     self.ctx.cur_act = index;
-                    succeed &= self.check_text_rich_error(act);
+                    succeed &= self.check_text_rich_error(act, || para.text_span(index));
                 }
                 if let Some(next) = &para.next {
-                    succeed &= self.check_text_rich_error(next);
+                    succeed &= self.check_text_rich_error(next, || None);
                 }
             }
         }
+        for d in self.diagnostics() {
+            eprintln!("{}", d);
+            succeed &= d.severity() != Severity::Error;
+        }
         succeed
     }
 }
@@ -0,0 +1,141 @@
+use crate::*;
+use std::fmt::Display;
+
+/// The severity of a [`Diagnostic`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The game could run, but it may not be expected.
+    Warning,
+    /// The game will fail at runtime.
+    Error,
+}
+
+/// The kind of a [`Diagnostic`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// The paragraph cannot be reached from the start paragraph.
+    Unreachable,
+    /// The paragraph references a tag which doesn't exist.
+    MissingTag(String),
+    /// The game never ends after the paragraph.
+    NoEnd,
+    /// The paragraph is missing in the locale,
+    /// and fallbacks to the base language.
+    MissingInLocale,
+    /// The paragraph only exists in the locale, not in the base language.
+    NotInBase,
+    /// The text command is not provided by any plugin.
+    UnknownCommand(String),
+    /// The text command is called with a wrong count of arguments.
+    ArgCount {
+        /// The command name.
+        name: String,
+        /// The declared count.
+        expected: usize,
+        /// The actual count.
+        found: usize,
+    },
+    /// The resource key is missing in the locale and the base language.
+    MissingResource(String),
+    /// The function is not exported by the plugin, or the plugin is not loaded.
+    UnknownFunction(String, String),
+    /// The context variable may be read before it is written,
+    /// following the paragraphs from the start.
+    UnwrittenVariable(String),
+    /// The unlock id is not defined in the game.
+    UnknownUnlock(String),
+}
+
+impl DiagnosticKind {
+    /// The severity of the diagnostic.
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::Unreachable
+            | Self::NoEnd
+            | Self::MissingInLocale
+            | Self::NotInBase
+            | Self::UnwrittenVariable(_) => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+/// A diagnostic of the static analysis of a [`Game`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The locale of the paragraph.
+    pub locale: Locale,
+    /// The tag of the paragraph.
+    pub tag: String,
+    /// The index of the text.
+    /// It is [`None`] for the paragraph itself and its `next`.
+    pub index: Option<usize>,
+    /// The position in the source file, if available.
+    pub pos: Option<SourcePos>,
+    /// The kind.
+    pub kind: DiagnosticKind,
+}
+
+impl Diagnostic {
+    /// Creates a [`Diagnostic`] of a paragraph.
+    pub fn new(locale: &Locale, tag: &str, kind: DiagnosticKind) -> Self {
+        Self {
+            locale: locale.clone(),
+            tag: tag.to_string(),
+            index: None,
+            pos: None,
+            kind,
+        }
+    }
+
+    /// The severity of the diagnostic.
+    pub fn severity(&self) -> Severity {
+        self.kind.severity()
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.severity() {
+            Severity::Warning => write!(f, "warning: ")?,
+            Severity::Error => write!(f, "error: ")?,
+        }
+        if let Some(pos) = &self.pos {
+            write!(f, "{}: ", pos)?;
+        }
+        write!(f, "paragraph `{}` ({})", self.tag, self.locale)?;
+        if let (None, Some(index)) = (&self.pos, self.index) {
+            write!(f, ", text {}", index)?;
+        }
+        write!(f, ": ")?;
+        match &self.kind {
+            DiagnosticKind::Unreachable => write!(f, "cannot be reached from the start"),
+            DiagnosticKind::MissingTag(t) => write!(f, "the next paragraph `{}` is missing", t),
+            DiagnosticKind::NoEnd => write!(f, "the game never ends after it"),
+            DiagnosticKind::MissingInLocale => write!(f, "missing in this locale"),
+            DiagnosticKind::NotInBase => write!(f, "missing in the base language"),
+            DiagnosticKind::UnknownCommand(name) => write!(f, "unknown command `{}`", name),
+            DiagnosticKind::ArgCount {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "command `{}` expects {} argument(s), but {} provided",
+                name, expected, found
+            ),
+            DiagnosticKind::MissingResource(key) => write!(f, "missing resource `{}`", key),
+            DiagnosticKind::UnknownFunction(ns, name) => {
+                if ns.is_empty() {
+                    write!(f, "unknown function `{}`", name)
+                } else {
+                    write!(f, "unknown function `{}.{}`", ns, name)
+                }
+            }
+            DiagnosticKind::UnwrittenVariable(name) => {
+                write!(f, "context variable `${}` may be read before written", name)
+            }
+            DiagnosticKind::UnknownUnlock(id) => write!(f, "unknown unlock `{}`", id),
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use crate::*;
+use serde::Serialize;
+
+/// The events emitted by [`Context`] when running the game.
+///
+/// Subscribe them with [`Context::subscribe`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "t", content = "data")]
+pub enum ContextEvent {
+    /// Entered a paragraph, before its `on_enter` script.
+    ParagraphChanged {
+        /// The tag of the paragraph.
+        tag: String,
+        /// The localized title of the paragraph.
+        title: Option<String>,
+    },
+    /// A context variable changed.
+    VariableChanged {
+        /// The name of the variable.
+        name: String,
+        /// The new value, or [`None`] if it is removed.
+        value: Option<RawValue>,
+    },
+    /// The current action changed, by stepping forward or back.
+    Action {
+        /// The current action.
+        action: Box<Action>,
+        /// Whether the action has been visited in the global record.
+        visited: bool,
+    },
+    /// The switches are presented with the current action.
+    SwitchPresented(Vec<Switch>),
+    /// The game ended after the paragraph.
+    GameEnded(String),
+}
+
+/// The callback of [`ContextEvent`].
+pub(crate) type Subscriber = Box<dyn FnMut(&ContextEvent) + Send + Sync>;
@@ -0,0 +1,480 @@
+use crate::{script::bin_cmp_logic, *};
+use gal_script::{
+    BinaryOp, Command, Expr, Line, LogicBinaryOp, Program, Ref, Text, TextParser, UnaryOp,
+};
+use serde::Serialize;
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    fmt::Write,
+};
+
+/// The possible targets of the `next` of a paragraph.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct NextTargets {
+    /// The tags of the target paragraphs.
+    pub tags: BTreeSet<String>,
+    /// Whether the game may end after the paragraph.
+    pub end: bool,
+    /// Whether the target is calculated at runtime,
+    /// and cannot be known statically.
+    pub dynamic: bool,
+}
+
+impl NextTargets {
+    /// Gets the possible targets of a `next` text.
+    ///
+    /// Only the string literals, and the `if` calls of them, are known statically.
+    pub fn new(next: Option<&str>) -> Self {
+        Self::with_env(next, &Env::new())
+    }
+
+    /// Gets the possible targets with some known context variables.
+    fn with_env(next: Option<&str>, env: &Env) -> Self {
+        let mut targets = Self::default();
+        let values = match next.map(|next| TextParser::new(next).parse()) {
+            Some(Ok(text)) => text_values(text, env),
+            // The game ends if there is no next, or it cannot be parsed.
+            _ => Some(BTreeSet::from([String::new()])),
+        };
+        match values {
+            Some(values) => {
+                for value in values {
+                    let value = value.trim();
+                    if value.is_empty() {
+                        targets.end = true;
+                    } else {
+                        targets.tags.insert(value.to_string());
+                    }
+                }
+            }
+            None => targets.dynamic = true,
+        }
+        targets
+    }
+}
+
+/// The known context variables.
+type Env = HashMap<String, RawValue>;
+
+/// The value of an expression, if it could be calculated statically.
+fn const_value(expr: &Expr, env: &Env) -> Option<RawValue> {
+    match expr {
+        Expr::Const(value) => Some(value.clone()),
+        Expr::Ref(Ref::Ctx(name)) => env.get(name).cloned(),
+        Expr::Unary(UnaryOp::Not, e) => match const_value(e, env)? {
+            RawValue::Bool(b) => Some(RawValue::Bool(!b)),
+            _ => None,
+        },
+        Expr::Binary(lhs, BinaryOp::Logic(op), rhs) => {
+            let lhs = const_value(lhs, env)?;
+            let rhs = const_value(rhs, env)?;
+            Some(RawValue::Bool(match op {
+                LogicBinaryOp::And => lhs.get_bool() && rhs.get_bool(),
+                LogicBinaryOp::Or => lhs.get_bool() || rhs.get_bool(),
+                op => bin_cmp_logic(&lhs, op, &rhs),
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// The possible values of an expression, or [`None`] if unknown.
+fn expr_values(expr: Option<&Expr>, env: &Env) -> Option<BTreeSet<String>> {
+    match expr {
+        None => Some(BTreeSet::from([String::new()])),
+        Some(Expr::Call(ns, name, args)) if ns.is_empty() && name == "if" => {
+            match args.first().and_then(|cond| const_value(cond, env)) {
+                Some(cond) => expr_values(args.get(if cond.get_bool() { 1 } else { 2 }), env),
+                None => {
+                    let mut values = expr_values(args.get(1), env)?;
+                    values.extend(expr_values(args.get(2), env)?);
+                    Some(values)
+                }
+            }
+        }
+        Some(expr) => const_value(expr, env).map(|value| BTreeSet::from([value.into_str()])),
+    }
+}
+
+fn text_values(text: Text, env: &Env) -> Option<BTreeSet<String>> {
+    let mut values = BTreeSet::from([String::new()]);
+    for line in text.0 {
+        let suffixes = match line {
+            Line::Str(s) => BTreeSet::from([s]),
+            Line::Cmd(Command::Exec(p)) => expr_values(p.0.last(), env)?,
+            Line::Cmd(_) => continue,
+        };
+        values = values
+            .iter()
+            .flat_map(|prefix| suffixes.iter().map(move |s| prefix.clone() + s))
+            .collect();
+    }
+    Some(values)
+}
+
+/// The context variables assigned with constants in a switch action.
+fn switch_env(action: &Program) -> Env {
+    let mut env = Env::new();
+    for expr in &action.0 {
+        if let Expr::Binary(lhs, op, rhs) = expr {
+            if let Expr::Ref(Ref::Ctx(name)) = lhs.as_ref() {
+                match (op, const_value(rhs, &env)) {
+                    (BinaryOp::Assign, Some(value)) => env.insert(name.clone(), value),
+                    _ => env.remove(name),
+                };
+            }
+        }
+    }
+    env
+}
+
+/// An edge in the [`StoryGraph`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StoryEdge {
+    /// The tag of the target paragraph.
+    /// If [`None`], the game meets the end.
+    pub to: Option<String>,
+    /// The texts of the switches leading to the target.
+    /// It is empty if the target doesn't depend on the switches.
+    pub switches: Vec<String>,
+}
+
+/// A paragraph in the [`StoryGraph`].
+#[derive(Debug, Clone, Serialize)]
+pub struct StoryNode {
+    /// The tag of the paragraph.
+    pub tag: String,
+    /// The title of the paragraph.
+    pub title: Option<String>,
+    /// The targets of `next`.
+    pub next: NextTargets,
+    /// The edges to the targets, labeled with switches.
+    pub edges: Vec<StoryEdge>,
+}
+
+impl StoryNode {
+    fn new(tag: &str, para: Option<&Paragraph>, next: Option<&str>) -> Self {
+        let targets = NextTargets::new(next);
+        let switches = para
+            .into_iter()
+            .flat_map(|p| &p.texts)
+            .filter_map(|t| TextParser::new(t).parse().ok())
+            .flat_map(|t| t.0)
+            .filter_map(|line| match line {
+                Line::Cmd(Command::Switch { text, action, .. }) => {
+                    Some((text, NextTargets::with_env(next, &switch_env(&action))))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let edges = targets
+            .tags
+            .iter()
+            .map(|t| Some(t.as_str()))
+            .chain(targets.end.then_some(None))
+            .map(|to| {
+                let mut labels = switches
+                    .iter()
+                    .filter(|(_, t)| {
+                        t.dynamic
+                            || match to {
+                                Some(to) => t.tags.contains(to),
+                                None => t.end,
+                            }
+                    })
+                    .map(|(text, _)| text.clone())
+                    .collect::<Vec<_>>();
+                if labels.len() == switches.len() {
+                    labels.clear();
+                }
+                StoryEdge {
+                    to: to.map(|s| s.to_string()),
+                    switches: labels,
+                }
+            })
+            .collect();
+        Self {
+            tag: tag.to_string(),
+            title: para.and_then(|p| p.title.clone()),
+            next: targets,
+            edges,
+        }
+    }
+}
+
+/// The graph of paragraphs linked by `next`, in a specified locale.
+///
+/// The paragraphs fallback to the base language as the runtime does.
+#[derive(Debug, Clone, Serialize)]
+pub struct StoryGraph {
+    /// The locale of the graph.
+    pub locale: Locale,
+    /// The tag of the start paragraph.
+    pub start: Option<String>,
+    /// The paragraphs, the ones in the base language first.
+    pub nodes: Vec<StoryNode>,
+}
+
+impl StoryGraph {
+    /// Builds the graph of the game in the locale.
+    pub fn new(game: &Game, loc: &Locale) -> Self {
+        let base_key = game.choose_from_keys(&game.base_lang, &game.paras);
+        let mut tags = vec![];
+        let mut visited = HashSet::new();
+        for key in std::iter::once(base_key).chain(game.fallback_chain(loc, &game.paras)) {
+            for p in game.paras.get(key).into_iter().flatten() {
+                if visited.insert(p.tag.as_str()) {
+                    tags.push(p.tag.as_str());
+                }
+            }
+        }
+        let nodes = tags
+            .into_iter()
+            .map(|tag| {
+                StoryNode::new(
+                    tag,
+                    game.find_para_fallback(loc, tag).fallback(),
+                    game.find_para_fallback(loc, tag)
+                        .and_then(|p| p.next.as_deref()),
+                )
+            })
+            .collect();
+        Self {
+            locale: loc.clone(),
+            start: game
+                .paras
+                .get(&game.base_lang)
+                .and_then(|paras| paras.first())
+                .map(|p| p.tag.clone()),
+            nodes,
+        }
+    }
+
+    /// Finds the node by tag.
+    pub fn node(&self, tag: &str) -> Option<&StoryNode> {
+        self.nodes.iter().find(|n| n.tag == tag)
+    }
+
+    /// The paragraphs reachable from the start paragraph.
+    pub fn reachable(&self) -> HashSet<&str> {
+        let mut reached = HashSet::new();
+        let mut queue = self.start.as_deref().into_iter().collect::<VecDeque<_>>();
+        while let Some(tag) = queue.pop_front() {
+            if let Some(node) = self.node(tag) {
+                if reached.insert(node.tag.as_str()) {
+                    queue.extend(node.next.tags.iter().map(|t| t.as_str()));
+                }
+            }
+        }
+        reached
+    }
+
+    /// The paragraphs from which the game could end.
+    ///
+    /// A dynamic target or a missing tag is treated as a possible end.
+    pub fn ending(&self) -> HashSet<&str> {
+        let mut prev = HashMap::<&str, Vec<&str>>::new();
+        let mut queue = VecDeque::new();
+        for node in &self.nodes {
+            if node.next.end
+                || node.next.dynamic
+                || node.next.tags.iter().any(|t| self.node(t).is_none())
+            {
+                queue.push_back(node.tag.as_str());
+            }
+            for t in &node.next.tags {
+                prev.entry(t.as_str()).or_default().push(node.tag.as_str());
+            }
+        }
+        let mut ending = HashSet::new();
+        while let Some(tag) = queue.pop_front() {
+            if ending.insert(tag) {
+                queue.extend(prev.get(tag).into_iter().flatten());
+            }
+        }
+        ending
+    }
+
+    /// Analyzes the graph.
+    ///
+    /// If a reachable paragraph has a dynamic target,
+    /// the unreachable paragraphs are not reported.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let reachable = self.reachable();
+        let ending = self.ending();
+        let check_reachable = !self
+            .nodes
+            .iter()
+            .any(|n| n.next.dynamic && reachable.contains(n.tag.as_str()));
+        let mut res = vec![];
+        for node in &self.nodes {
+            let mut push = |kind| res.push(Diagnostic::new(&self.locale, &node.tag, kind));
+            if check_reachable && !reachable.contains(node.tag.as_str()) {
+                push(DiagnosticKind::Unreachable);
+            }
+            for t in &node.next.tags {
+                if self.node(t).is_none() {
+                    push(DiagnosticKind::MissingTag(t.clone()));
+                }
+            }
+            if !ending.contains(node.tag.as_str()) {
+                push(DiagnosticKind::NoEnd);
+            }
+        }
+        res
+    }
+
+    /// Writes the graph in Graphviz DOT format.
+    ///
+    /// The paragraphs are labeled with titles, and the edges with switch texts.
+    pub fn to_dot(&self) -> String {
+        fn quote(s: &str) -> String {
+            let s = s.replace('\\', "\\\\").replace('"', "\\\"");
+            format!("\"{}\"", s.replace('\n', "\\n"))
+        }
+
+        // The end node should not conflict with any tag.
+        let mut end = "[end]".to_string();
+        while self.node(&end).is_some() {
+            end.push('\'');
+        }
+        let mut dot = String::new();
+        writeln!(dot, "digraph {} {{", quote(&self.locale.to_string())).unwrap();
+        writeln!(dot, "    node [shape=box];").unwrap();
+        writeln!(dot, "    {} [shape=point];", quote(&end)).unwrap();
+        for node in &self.nodes {
+            let mut attrs = vec![format!(
+                "label={}",
+                quote(node.title.as_deref().unwrap_or(&node.tag))
+            )];
+            if self.start.as_ref() == Some(&node.tag) {
+                attrs.push("style=bold".to_string());
+            }
+            if node.next.dynamic {
+                attrs.push("color=gray".to_string());
+            }
+            writeln!(dot, "    {} [{}];", quote(&node.tag), attrs.join(", ")).unwrap();
+        }
+        for node in &self.nodes {
+            for edge in &node.edges {
+                write!(
+                    dot,
+                    "    {} -> {}",
+                    quote(&node.tag),
+                    quote(edge.to.as_deref().unwrap_or(&end))
+                )
+                .unwrap();
+                if !edge.switches.is_empty() {
+                    write!(dot, " [label={}]", quote(&edge.switches.join("\n"))).unwrap();
+                }
+                writeln!(dot, ";").unwrap();
+            }
+        }
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+}
+
+impl Game {
+    /// The locales of the paragraphs, the base language first.
+    pub(crate) fn para_locales(&self) -> Vec<&Locale> {
+        let base_key = self.choose_from_keys(&self.base_lang, &self.paras);
+        let mut locales = self
+            .paras
+            .keys()
+            .filter(|&loc| loc != base_key)
+            .collect::<Vec<_>>();
+        locales.sort_by_key(|loc| loc.to_string());
+        locales.insert(0, base_key);
+        locales
+    }
+
+    /// Builds the paragraph graphs in all locales, the base language first.
+    pub fn story_graphs(&self) -> Vec<StoryGraph> {
+        self.para_locales()
+            .into_iter()
+            .map(|loc| StoryGraph::new(self, loc))
+            .collect()
+    }
+
+    /// Analyzes the paragraph graph in all locales.
+    ///
+    /// The diagnostics of the base language are reported first.
+    /// For other locales, only the different ones are reported.
+    pub fn analyze(&self) -> Vec<Diagnostic> {
+        let locales = self.para_locales();
+        let base_key = locales[0];
+        let mut res = StoryGraph::new(self, base_key).diagnostics();
+        let base_paras = self
+            .paras
+            .get(base_key)
+            .map(|p| p.as_slice())
+            .unwrap_or_default();
+        let base_tags = base_paras
+            .iter()
+            .map(|p| p.tag.as_str())
+            .collect::<HashSet<_>>();
+        for &loc in &locales[1..] {
+            let tags = self.paras[loc]
+                .iter()
+                .map(|p| p.tag.as_str())
+                .collect::<HashSet<_>>();
+            for p in base_paras {
+                if !tags.contains(p.tag.as_str()) {
+                    res.push(Diagnostic::new(
+                        loc,
+                        &p.tag,
+                        DiagnosticKind::MissingInLocale,
+                    ));
+                }
+            }
+            for p in &self.paras[loc] {
+                if !base_tags.contains(p.tag.as_str()) {
+                    res.push(Diagnostic::new(loc, &p.tag, DiagnosticKind::NotInBase));
+                }
+            }
+            for d in StoryGraph::new(self, loc).diagnostics() {
+                let in_base = res
+                    .iter()
+                    .any(|b| &b.locale == base_key && b.tag == d.tag && b.kind == d.kind);
+                if !in_base {
+                    res.push(d);
+                }
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+
+    fn targets(tags: &[&str], end: bool, dynamic: bool) -> NextTargets {
+        NextTargets {
+            tags: tags.iter().map(|s| s.to_string()).collect(),
+            end,
+            dynamic,
+        }
+    }
+
+    #[test]
+    fn next() {
+        let next = |s| NextTargets::new(Some(s));
+        assert_eq!(NextTargets::new(None), targets(&[], true, false));
+        assert_eq!(next("loop"), targets(&["loop"], false, false));
+        assert_eq!(
+            next("\\exec{if($i < $n, \"loop\")}"),
+            targets(&["loop"], true, false)
+        );
+        assert_eq!(
+            next("\\exec{$n -= 1; if($n, \"a\", if($m, \"b\", \"c\"))}"),
+            targets(&["a", "b", "c"], false, false)
+        );
+        assert_eq!(
+            next("chapter\\exec{if($good, 1, 2)}"),
+            targets(&["chapter1", "chapter2"], false, false)
+        );
+        assert_eq!(next("\\exec{$next}"), targets(&[], false, true));
+    }
+}
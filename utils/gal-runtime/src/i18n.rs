@@ -0,0 +1,309 @@
+use crate::*;
+use gal_script::{Command, Line, Text, TextParser};
+use serde::Serialize;
+use std::fmt::Display;
+
+/// The kind of an [`I18nIssue`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum I18nIssueKind {
+    /// The paragraph is missing in the locale.
+    MissingPara,
+    /// The text is missing or empty in the locale.
+    MissingText,
+    /// The text only exists in the locale.
+    ExtraText,
+    /// The count of switches is different from the base language.
+    SwitchCount {
+        /// The count in the base language.
+        base: usize,
+        /// The count in the locale.
+        found: usize,
+    },
+    /// The characters in `\ch` are different from the base language.
+    Character {
+        /// The characters in the base language.
+        base: Vec<String>,
+        /// The characters in the locale.
+        found: Vec<String>,
+    },
+    /// The resource key is missing in the locale.
+    MissingRes(String),
+    /// The text is identical to the base language.
+    Untranslated,
+}
+
+/// An issue of a locale, compared with the base language.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct I18nIssue {
+    /// The tag of the paragraph.
+    /// It is [`None`] for the resources.
+    pub tag: Option<String>,
+    /// The index of the text.
+    pub index: Option<usize>,
+    /// The kind.
+    pub kind: I18nIssueKind,
+}
+
+impl I18nIssue {
+    fn para(tag: &str, index: Option<usize>, kind: I18nIssueKind) -> Self {
+        Self {
+            tag: Some(tag.to_string()),
+            index,
+            kind,
+        }
+    }
+}
+
+impl Display for I18nIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.tag {
+            Some(tag) => write!(f, "paragraph `{}`", tag)?,
+            None => write!(f, "resources")?,
+        }
+        if let Some(index) = self.index {
+            write!(f, ", text {}", index)?;
+        }
+        write!(f, ": ")?;
+        match &self.kind {
+            I18nIssueKind::MissingPara => write!(f, "missing"),
+            I18nIssueKind::MissingText => write!(f, "missing"),
+            I18nIssueKind::ExtraText => write!(f, "not in the base language"),
+            I18nIssueKind::SwitchCount { base, found } => {
+                write!(f, "{} switch(es), but {} in the base language", found, base)
+            }
+            I18nIssueKind::Character { base, found } => write!(
+                f,
+                "characters [{}], but [{}] in the base language",
+                found.join(", "),
+                base.join(", ")
+            ),
+            I18nIssueKind::MissingRes(key) => write!(f, "missing `{}`", key),
+            I18nIssueKind::Untranslated => write!(f, "identical to the base language"),
+        }
+    }
+}
+
+/// The translation report of a locale.
+#[derive(Debug, Clone, Serialize)]
+pub struct LocaleReport {
+    /// The locale.
+    pub locale: Locale,
+    /// The count of texts in the base language.
+    pub texts: usize,
+    /// The count of texts translated in the locale.
+    pub translated: usize,
+    /// The issues.
+    pub issues: Vec<I18nIssue>,
+}
+
+impl LocaleReport {
+    /// The percentage of the translated texts.
+    pub fn completeness(&self) -> f64 {
+        if self.texts == 0 {
+            100.0
+        } else {
+            self.translated as f64 * 100.0 / self.texts as f64
+        }
+    }
+}
+
+impl Display for LocaleReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{}: {:.1}% ({}/{} texts translated)",
+            self.locale,
+            self.completeness(),
+            self.translated,
+            self.texts
+        )?;
+        for issue in &self.issues {
+            writeln!(f, "  {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+/// The translation report of a [`Game`], compared with the base language.
+#[derive(Debug, Clone, Serialize)]
+pub struct I18nReport {
+    /// The base language.
+    pub base: Locale,
+    /// The reports of other locales.
+    pub locales: Vec<LocaleReport>,
+}
+
+impl Display for I18nReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for report in &self.locales {
+            write!(f, "{}", report)?;
+        }
+        Ok(())
+    }
+}
+
+/// The switch count and the characters of a text.
+fn text_shape(text: &Text) -> (usize, Vec<String>) {
+    let mut switches = 0;
+    let mut chs = vec![];
+    for line in &text.0 {
+        match line {
+            Line::Cmd(Command::Switch { .. }) => switches += 1,
+            Line::Cmd(Command::Character(key, _)) => chs.push(key.clone()),
+            _ => {}
+        }
+    }
+    (switches, chs)
+}
+
+/// Determines whether the text contains words to translate.
+fn has_words(text: &Text) -> bool {
+    text.0
+        .iter()
+        .any(|line| matches!(line, Line::Str(s) if !s.trim().is_empty()))
+}
+
+fn compare_text(tag: &str, index: usize, base: &str, text: &str, issues: &mut Vec<I18nIssue>) {
+    let (base_text, loc_text) = match (TextParser::new(base).parse(), TextParser::new(text).parse())
+    {
+        (Ok(base_text), Ok(loc_text)) => (base_text, loc_text),
+        _ => return,
+    };
+    let (base_switches, base_chs) = text_shape(&base_text);
+    let (switches, chs) = text_shape(&loc_text);
+    if base_switches != switches {
+        issues.push(I18nIssue::para(
+            tag,
+            Some(index),
+            I18nIssueKind::SwitchCount {
+                base: base_switches,
+                found: switches,
+            },
+        ));
+    }
+    // The characters fallback to the base language if not specified.
+    if !chs.is_empty() && base_chs != chs {
+        issues.push(I18nIssue::para(
+            tag,
+            Some(index),
+            I18nIssueKind::Character {
+                base: base_chs,
+                found: chs,
+            },
+        ));
+    }
+    // Without words, the text falls back to the base language.
+    if has_words(&base_text) && !has_words(&loc_text) {
+        issues.push(I18nIssue::para(
+            tag,
+            Some(index),
+            I18nIssueKind::MissingText,
+        ));
+    }
+    if base.trim() == text.trim() && has_words(&base_text) {
+        issues.push(I18nIssue::para(
+            tag,
+            Some(index),
+            I18nIssueKind::Untranslated,
+        ));
+    }
+}
+
+impl Game {
+    /// Reports the translation completeness and parity of all locales,
+    /// compared with the base language.
+    ///
+    /// The texts are compared by index.
+    /// The locales are sorted, and the base language is not included.
+    pub fn i18n_report(&self) -> I18nReport {
+        let base_paras_key = self.choose_from_keys(&self.base_lang, &self.paras);
+        let base_res_key = self.choose_from_keys(&self.base_lang, &self.res);
+        let mut locales = self
+            .paras
+            .keys()
+            .chain(self.res.keys())
+            .filter(|&loc| loc != base_paras_key && loc != base_res_key)
+            .collect::<Vec<_>>();
+        locales.sort_by_key(|loc| loc.to_string());
+        locales.dedup();
+        I18nReport {
+            base: base_paras_key.clone(),
+            locales: locales
+                .into_iter()
+                .map(|loc| self.locale_report(loc, base_paras_key, base_res_key))
+                .collect(),
+        }
+    }
+
+    fn locale_report(
+        &self,
+        loc: &Locale,
+        base_paras_key: &Locale,
+        base_res_key: &Locale,
+    ) -> LocaleReport {
+        let mut texts = 0;
+        let mut translated = 0;
+        let mut issues = vec![];
+        let paras = self.paras.get(loc);
+        for base in self.paras.get(base_paras_key).into_iter().flatten() {
+            texts += base.texts.len();
+            let para = match paras.and_then(|paras| paras.iter().find(|p| p.tag == base.tag)) {
+                Some(para) => para,
+                None => {
+                    issues.push(I18nIssue::para(&base.tag, None, I18nIssueKind::MissingPara));
+                    continue;
+                }
+            };
+            for (index, base_text) in base.texts.iter().enumerate() {
+                // The empty texts fallback to the base language.
+                match para.texts.get(index).filter(|s| !s.is_empty() && *s != "~") {
+                    Some(text) => {
+                        let len = issues.len();
+                        compare_text(&base.tag, index, base_text, text, &mut issues);
+                        if !issues[len..].iter().any(|i| {
+                            matches!(
+                                i.kind,
+                                I18nIssueKind::Untranslated | I18nIssueKind::MissingText
+                            )
+                        }) {
+                            translated += 1;
+                        }
+                    }
+                    None => issues.push(I18nIssue::para(
+                        &base.tag,
+                        Some(index),
+                        I18nIssueKind::MissingText,
+                    )),
+                }
+            }
+            for index in base.texts.len()..para.texts.len() {
+                issues.push(I18nIssue::para(
+                    &base.tag,
+                    Some(index),
+                    I18nIssueKind::ExtraText,
+                ));
+            }
+        }
+        let res = self.res.get(loc);
+        let mut keys = self
+            .res
+            .get(base_res_key)
+            .into_iter()
+            .flat_map(|map| map.keys())
+            .filter(|key| !res.map(|res| res.contains_key(*key)).unwrap_or_default())
+            .collect::<Vec<_>>();
+        keys.sort();
+        issues.extend(keys.into_iter().map(|key| I18nIssue {
+            tag: None,
+            index: None,
+            kind: I18nIssueKind::MissingRes(key.clone()),
+        }));
+        LocaleReport {
+            locale: loc.clone(),
+            texts,
+            translated,
+            issues,
+        }
+    }
+}
@@ -10,17 +10,35 @@
 #![feature(generators)]
 #![feature(round_char_boundary)]
 
+mod check;
 mod config;
 mod context;
+mod diagnostic;
+mod event;
+mod graph;
+mod i18n;
+mod migration;
 pub mod plugin;
+mod po;
 pub mod script;
 mod settings;
+mod span;
+mod storage;
 
 pub use anyhow;
 pub use config::*;
 pub use context::*;
+pub use diagnostic::*;
+pub use event::*;
 pub use futures_util::{pin_mut, StreamExt, TryStreamExt};
+pub use gal_primitive::PropValue;
 pub use gal_script::{log, RawValue};
+pub use graph::*;
+pub use i18n::*;
 pub use locale::*;
+pub use migration::*;
+pub use po::*;
 pub use settings::*;
+pub use span::*;
+pub use storage::*;
 pub use stream_future::*;
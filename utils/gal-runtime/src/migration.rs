@@ -0,0 +1,158 @@
+use crate::*;
+use anyhow::{bail, Result};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{Read, Write};
+
+/// A migration step of a save file.
+///
+/// It upgrades the JSON value of the data by one version.
+pub type Migration = fn(Value) -> Result<Value>;
+
+/// A save file type with a version.
+///
+/// The data is wrapped in an envelope `{ "version": _, "data": _ }` when saving,
+/// and the older versions are upgraded step by step when loading.
+pub trait Versioned: Serialize + DeserializeOwned {
+    /// The registered migrations.
+    ///
+    /// The `i`-th migration upgrades the data from version `i` to `i + 1`.
+    /// The files saved before the envelope was introduced are version 0.
+    const MIGRATIONS: &'static [Migration];
+
+    /// The current version.
+    const VERSION: usize = Self::MIGRATIONS.len();
+}
+
+/// Migrates the unversioned data, which has the same layout as version 1.
+fn from_unversioned(value: Value) -> Result<Value> {
+    Ok(value)
+}
+
+/// Migrates the single display language to a list of preferences.
+fn lang_to_preferences(mut value: Value) -> Result<Value> {
+    if let Some(lang) = value.get_mut("lang") {
+        if lang.is_string() {
+            *lang = Value::Array(vec![lang.take()]);
+        }
+    }
+    Ok(value)
+}
+
+impl Versioned for Settings {
+    const MIGRATIONS: &'static [Migration] = &[from_unversioned, lang_to_preferences];
+}
+
+impl Versioned for GlobalRecord {
+    const MIGRATIONS: &'static [Migration] = &[from_unversioned];
+}
+
+impl Versioned for ActionRecord {
+    const MIGRATIONS: &'static [Migration] = &[from_unversioned];
+}
+
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    version: usize,
+    data: &'a T,
+}
+
+fn split_envelope(value: Value) -> (usize, Value) {
+    match value {
+        Value::Object(mut map) if map.len() == 2 && map.contains_key("data") => {
+            match map.get("version").and_then(|v| v.as_u64()) {
+                Some(version) => {
+                    // unwrap: checked above.
+                    let data = map.remove("data").unwrap();
+                    (version as usize, data)
+                }
+                None => (0, Value::Object(map)),
+            }
+        }
+        value => (0, value),
+    }
+}
+
+/// Upgrades a JSON value of a save file to the current version of `T`.
+pub fn migrate<T: Versioned>(value: Value) -> Result<T> {
+    let (version, mut data) = split_envelope(value);
+    if version > T::VERSION {
+        bail!(
+            "The save version {} is newer than the supported version {}.",
+            version,
+            T::VERSION
+        );
+    }
+    for (i, m) in T::MIGRATIONS.iter().enumerate().skip(version) {
+        data = m(data).map_err(|e| e.context(format!("Migrating from version {}", i)))?;
+    }
+    Ok(serde_json::from_value(data)?)
+}
+
+/// The format of a save file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SaveFormat {
+    /// Compact JSON.
+    #[default]
+    Json,
+    /// Pretty JSON, which is easy to edit by hand.
+    PrettyJson,
+    /// Gzip-compressed MessagePack, which is much smaller for long histories.
+    Binary,
+}
+
+impl SaveFormat {
+    /// The file extension of the format.
+    pub const fn extension(&self) -> &'static str {
+        match self {
+            Self::Json | Self::PrettyJson => "json",
+            Self::Binary => "bin",
+        }
+    }
+
+    /// Detects the format of the save file content.
+    pub fn detect(buffer: &[u8]) -> Self {
+        if buffer.starts_with(&GZIP_MAGIC) {
+            Self::Binary
+        } else {
+            Self::Json
+        }
+    }
+}
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Deserializes a save file, upgrading it to the current version.
+///
+/// The format is detected from the content.
+pub fn from_versioned_slice<T: Versioned>(buffer: &[u8]) -> Result<T> {
+    let value = match SaveFormat::detect(buffer) {
+        SaveFormat::Json | SaveFormat::PrettyJson => serde_json::from_slice(buffer)?,
+        SaveFormat::Binary => {
+            let mut data = vec![];
+            GzDecoder::new(buffer).read_to_end(&mut data)?;
+            rmp_serde::from_slice(&data)?
+        }
+    };
+    migrate(value)
+}
+
+/// Serializes a save file with the current version.
+pub fn to_versioned_vec<T: Versioned>(data: &T, format: SaveFormat) -> Result<Vec<u8>> {
+    let envelope = Envelope {
+        version: T::VERSION,
+        data,
+    };
+    Ok(match format {
+        SaveFormat::Json => serde_json::to_vec(&envelope)?,
+        SaveFormat::PrettyJson => serde_json::to_vec_pretty(&envelope)?,
+        SaveFormat::Binary => {
+            // The field names are kept for migrations.
+            let data = rmp_serde::to_vec_named(&envelope)?;
+            let mut encoder = GzEncoder::new(vec![], Compression::default());
+            encoder.write_all(&data)?;
+            encoder.finish()?
+        }
+    })
+}
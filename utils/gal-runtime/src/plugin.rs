@@ -83,6 +83,11 @@ impl Host {
         self.call(name, (args,))
     }
 
+    /// Determines if the plugin exports a method.
+    pub fn has_method(&self, name: &str) -> bool {
+        self.instance.exports.get_function(name).is_ok()
+    }
+
     /// Gets the [`PluginType`].
     pub fn plugin_type(&self) -> Result<PluginType> {
         self.call("plugin_type", ())
@@ -122,6 +127,8 @@ pub struct Runtime {
     pub action_modules: Vec<String>,
     /// The text plugins by command name.
     pub text_modules: HashMap<String, String>,
+    /// The declared argument counts by command name.
+    pub text_args: HashMap<String, usize>,
     /// The game plugins.
     pub game_modules: Vec<String>,
 }
@@ -192,6 +199,7 @@ impl Runtime {
         let mut modules = HashMap::new();
         let mut action_modules = vec![];
         let mut text_modules = HashMap::new();
+        let mut text_args = HashMap::new();
         let mut game_modules = vec![];
         let paths = if names.is_empty() {
             ReadDirStream::new(tokio::fs::read_dir(path).await?)
@@ -247,6 +255,7 @@ impl Runtime {
                     );
                 }
             }
+            text_args.extend(plugin_type.text_args);
             if plugin_type.game {
                 game_modules.push(name.clone());
             }
@@ -256,6 +265,7 @@ impl Runtime {
             modules,
             action_modules,
             text_modules,
+            text_args,
             game_modules,
         })
     }
@@ -0,0 +1,452 @@
+use crate::*;
+use anyhow::{anyhow, bail, Result};
+use gal_script::{Command, Line, TextParser};
+use std::{collections::HashMap, fmt::Display};
+
+/// An entry in a gettext PO catalog.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PoEntry {
+    /// The context key.
+    pub msgctxt: String,
+    /// The source text.
+    pub msgid: String,
+    /// The translated text.
+    /// It is empty if not translated.
+    pub msgstr: String,
+    /// The extracted comments for translators.
+    pub comments: Vec<String>,
+    /// If the translation is fuzzy, it is ignored when importing.
+    pub fuzzy: bool,
+}
+
+/// A gettext PO catalog.
+///
+/// The context keys of the entries are:
+/// * `tag/index` for a text of a paragraph,
+/// * `tag/index/switch/i` for a switch label in the text,
+/// * `#key` for a string resource.
+///
+/// The commands in the texts are replaced with placeholders like `{0}`,
+/// and the switches are extracted as separate entries.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PoCatalog {
+    /// The target locale.
+    pub locale: Option<Locale>,
+    /// The entries, without the header.
+    pub entries: Vec<PoEntry>,
+}
+
+fn escape_po(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => res.push_str("\\\\"),
+            '"' => res.push_str("\\\""),
+            '\n' => res.push_str("\\n"),
+            '\t' => res.push_str("\\t"),
+            '\r' => res.push_str("\\r"),
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
+fn unescape_po(s: &str) -> Result<String> {
+    let mut res = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => res.push('\n'),
+                Some('t') => res.push('\t'),
+                Some('r') => res.push('\r'),
+                Some(c @ ('\\' | '"')) => res.push(c),
+                Some(c) => bail!("Unknown escape \"\\{}\".", c),
+                None => bail!("Unexpected end of string."),
+            }
+        } else {
+            res.push(c);
+        }
+    }
+    Ok(res)
+}
+
+fn write_po_string(f: &mut std::fmt::Formatter<'_>, key: &str, s: &str) -> std::fmt::Result {
+    if s.contains('\n') {
+        writeln!(f, "{} \"\"", key)?;
+        for line in s.split_inclusive('\n') {
+            writeln!(f, "\"{}\"", escape_po(line))?;
+        }
+        Ok(())
+    } else {
+        writeln!(f, "{} \"{}\"", key, escape_po(s))
+    }
+}
+
+impl Display for PoCatalog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut header = "Content-Type: text/plain; charset=UTF-8\n".to_string();
+        if let Some(loc) = &self.locale {
+            header.push_str(&format!("Language: {}\n", loc));
+        }
+        write_po_string(f, "msgid", "")?;
+        write_po_string(f, "msgstr", &header)?;
+        for entry in &self.entries {
+            writeln!(f)?;
+            for comment in &entry.comments {
+                writeln!(f, "#. {}", comment)?;
+            }
+            if entry.fuzzy {
+                writeln!(f, "#, fuzzy")?;
+            }
+            write_po_string(f, "msgctxt", &entry.msgctxt)?;
+            write_po_string(f, "msgid", &entry.msgid)?;
+            write_po_string(f, "msgstr", &entry.msgstr)?;
+        }
+        Ok(())
+    }
+}
+
+impl PoCatalog {
+    /// Parses a PO catalog.
+    ///
+    /// The plural forms are not supported.
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut entries = vec![];
+        let mut entry = PoEntry::default();
+        // The current keyword and whether the entry has any keyword.
+        let mut key = None;
+        let mut has_key = false;
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim();
+            let err = |msg: String| anyhow!("{}: {}", i + 1, msg);
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(comment) = line.strip_prefix('#') {
+                if has_key {
+                    entries.push(std::mem::take(&mut entry));
+                    has_key = false;
+                    key = None;
+                }
+                if let Some(flags) = comment.strip_prefix(',') {
+                    entry.fuzzy |= flags.split(',').any(|flag| flag.trim() == "fuzzy");
+                } else if let Some(comment) = comment.strip_prefix('.') {
+                    entry.comments.push(comment.trim().to_string());
+                }
+                continue;
+            }
+            let (keyword, value) = if line.starts_with('"') {
+                (key.ok_or_else(|| err("Unexpected string.".into()))?, line)
+            } else {
+                let (keyword, value) = line
+                    .split_once(char::is_whitespace)
+                    .ok_or_else(|| err(format!("Invalid line \"{}\".", line)))?;
+                let keyword = match keyword {
+                    "msgctxt" => "msgctxt",
+                    "msgid" => "msgid",
+                    "msgstr" => "msgstr",
+                    _ => return Err(err(format!("Unsupported keyword `{}`.", keyword))),
+                };
+                // A new entry starts with `msgctxt`, or `msgid` without `msgctxt`.
+                let new_entry =
+                    keyword == "msgctxt" || (keyword == "msgid" && key != Some("msgctxt"));
+                if has_key && new_entry {
+                    entries.push(std::mem::take(&mut entry));
+                }
+                has_key = true;
+                key = Some(keyword);
+                (keyword, value.trim())
+            };
+            let value = value
+                .strip_prefix('"')
+                .and_then(|s| s.strip_suffix('"'))
+                .ok_or_else(|| err(format!("Invalid string {}.", value)))?;
+            let value = unescape_po(value).map_err(|e| err(e.to_string()))?;
+            match keyword {
+                "msgctxt" => entry.msgctxt.push_str(&value),
+                "msgid" => entry.msgid.push_str(&value),
+                _ => entry.msgstr.push_str(&value),
+            }
+        }
+        if has_key {
+            entries.push(entry);
+        }
+        // The header entry.
+        let mut locale = None;
+        if let Some(index) = entries
+            .iter()
+            .position(|e| e.msgctxt.is_empty() && e.msgid.is_empty())
+        {
+            let header = entries.remove(index);
+            locale = header
+                .msgstr
+                .lines()
+                .filter_map(|line| line.strip_prefix("Language:"))
+                .find_map(|loc| loc.trim().parse().ok());
+        }
+        Ok(Self { locale, entries })
+    }
+
+    fn translations(&self) -> HashMap<&str, &str> {
+        self.entries
+            .iter()
+            .filter(|e| !e.fuzzy && !e.msgstr.is_empty())
+            .map(|e| (e.msgctxt.as_str(), e.msgstr.as_str()))
+            .collect()
+    }
+}
+
+/// A text split into a message with placeholders, and the switch labels.
+#[derive(Debug, PartialEq, Eq)]
+struct Message {
+    id: String,
+    // The sources of the commands.
+    commands: Vec<String>,
+    switches: Vec<String>,
+    // The message contains words to translate.
+    has_words: bool,
+}
+
+impl Message {
+    fn new(text: &str) -> Option<Self> {
+        let lines = TextParser::new(text).parse_with_locs().ok()?;
+        let mut id = String::new();
+        let mut commands = vec![];
+        let mut switches = vec![];
+        let mut has_words = false;
+        for (loc, line) in lines {
+            match line {
+                Line::Str(s) => {
+                    has_words |= !s.trim().is_empty();
+                    id.push_str(&s);
+                }
+                Line::Cmd(Command::Switch { text, .. }) => switches.push(text),
+                Line::Cmd(_) => {
+                    id.push_str(&format!("{{{}}}", commands.len()));
+                    commands.push(text[loc.0..loc.1].to_string());
+                }
+            }
+        }
+        Some(Self {
+            id: id.trim().to_string(),
+            commands,
+            switches,
+            has_words,
+        })
+    }
+
+    /// Replaces the placeholders with the commands, and escapes the others.
+    ///
+    /// The untranslated switch labels are left empty to fallback.
+    /// Without the translated text, the switches are not separated by new lines,
+    /// so that the text is empty and falls back.
+    fn render(&self, translated: &str, switches: &[Option<&str>]) -> String {
+        let mut res = String::new();
+        let mut rest = translated;
+        while let Some(c) = rest.chars().next() {
+            let placeholder = rest.strip_prefix('{').and_then(|s| {
+                let (num, s) = s.split_once('}')?;
+                let cmd = self.commands.get(num.parse::<usize>().ok()?)?;
+                Some((cmd, s))
+            });
+            if let Some((cmd, s)) = placeholder {
+                res.push_str(cmd);
+                // Avoid the command name being joined with the following text.
+                if !cmd.ends_with(['}', '/']) {
+                    res.push(' ');
+                }
+                rest = s;
+                continue;
+            }
+            if matches!(c, '\\' | '{' | '}' | '/') {
+                res.push('\\');
+            }
+            res.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+        let sep = if res.is_empty() { "" } else { "\n" };
+        for label in switches {
+            res.push_str(sep);
+            res.push_str("\\switch{");
+            for c in label.unwrap_or_default().chars() {
+                if matches!(c, '\\' | '{' | '}' | '/') {
+                    res.push('\\');
+                }
+                res.push(c);
+            }
+            res.push('}');
+        }
+        res
+    }
+}
+
+fn text_context(tag: &str, index: usize) -> String {
+    format!("{}/{}", tag, index)
+}
+
+fn switch_context(tag: &str, index: usize, i: usize) -> String {
+    format!("{}/{}/switch/{}", tag, index, i)
+}
+
+impl Game {
+    /// Exports the paragraph texts, switch labels and string resources
+    /// of the base language to a PO catalog for the locale.
+    ///
+    /// The existing translations in the locale are filled in,
+    /// if the commands in the texts are the same.
+    pub fn export_po(&self, loc: &Locale) -> PoCatalog {
+        let base_key = self.choose_from_keys(&self.base_lang, &self.paras);
+        let mut entries = vec![];
+        for para in self.paras.get(base_key).into_iter().flatten() {
+            let loc_para = self
+                .paras
+                .get(loc)
+                .filter(|_| loc != base_key)
+                .and_then(|paras| paras.iter().find(|p| p.tag == para.tag));
+            for (index, text) in para.texts.iter().enumerate() {
+                let msg = match Message::new(text) {
+                    Some(msg) => msg,
+                    None => continue,
+                };
+                let loc_msg = loc_para
+                    .and_then(|p| p.texts.get(index))
+                    .filter(|s| !s.is_empty() && *s != "~")
+                    .and_then(|s| Message::new(s));
+                if msg.has_words {
+                    let msgstr = loc_msg
+                        .as_ref()
+                        .filter(|m| m.commands == msg.commands)
+                        .map(|m| m.id.clone())
+                        .unwrap_or_default();
+                    entries.push(PoEntry {
+                        msgctxt: text_context(&para.tag, index),
+                        comments: msg
+                            .commands
+                            .iter()
+                            .enumerate()
+                            .map(|(i, cmd)| format!("{{{}}}: {}", i, cmd))
+                            .collect(),
+                        msgid: msg.id.clone(),
+                        msgstr,
+                        fuzzy: false,
+                    });
+                }
+                for (i, label) in msg.switches.iter().enumerate() {
+                    entries.push(PoEntry {
+                        msgctxt: switch_context(&para.tag, index, i),
+                        msgid: label.clone(),
+                        msgstr: loc_msg
+                            .as_ref()
+                            .and_then(|m| m.switches.get(i).cloned())
+                            .unwrap_or_default(),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+        let base_res_key = self.choose_from_keys(&self.base_lang, &self.res);
+        let loc_res = self.res.get(loc).filter(|_| loc != base_res_key);
+        let mut keys = self
+            .res
+            .get(base_res_key)
+            .into_iter()
+            .flatten()
+            .filter_map(|(key, value)| match value {
+                RawValue::Str(s) => Some((key, s)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        keys.sort();
+        for (key, value) in keys {
+            entries.push(PoEntry {
+                msgctxt: format!("#{}", key),
+                msgid: value.clone(),
+                msgstr: match loc_res.and_then(|res| res.get(key)) {
+                    Some(RawValue::Str(s)) => s.clone(),
+                    _ => String::new(),
+                },
+                ..Default::default()
+            });
+        }
+        PoCatalog {
+            locale: Some(loc.clone()),
+            entries,
+        }
+    }
+
+    /// Imports a translated PO catalog into the paragraphs and resources of the locale.
+    ///
+    /// The placeholders are replaced with the commands in the base language.
+    /// The untranslated texts are left empty to fallback.
+    pub fn import_po(&mut self, loc: &Locale, catalog: &PoCatalog) -> Result<()> {
+        let base_key = self.choose_from_keys(&self.base_lang, &self.paras);
+        if loc == base_key {
+            bail!("Cannot import translations into the base language {}.", loc);
+        }
+        let trans = catalog.translations();
+        let mut texts = vec![];
+        for para in self.paras.get(base_key).into_iter().flatten() {
+            for (index, text) in para.texts.iter().enumerate() {
+                let msg = match Message::new(text) {
+                    Some(msg) => msg,
+                    None => continue,
+                };
+                let translated = trans.get(text_context(&para.tag, index).as_str());
+                let switches = (0..msg.switches.len())
+                    .map(|i| {
+                        trans
+                            .get(switch_context(&para.tag, index, i).as_str())
+                            .copied()
+                    })
+                    .collect::<Vec<_>>();
+                if translated.is_none() && switches.iter().all(|s| s.is_none()) {
+                    continue;
+                }
+                // The untranslated text is left empty to fallback.
+                let id = if msg.has_words {
+                    translated.copied().unwrap_or_default().to_string()
+                } else {
+                    (0..msg.commands.len())
+                        .map(|i| format!("{{{}}}", i))
+                        .collect()
+                };
+                texts.push((para.tag.clone(), index, msg.render(&id, &switches)));
+            }
+        }
+        let paras = self.paras.entry(loc.clone()).or_default();
+        for (tag, index, text) in texts {
+            let para = match paras.iter().position(|p| p.tag == tag) {
+                Some(i) => &mut paras[i],
+                None => {
+                    paras.push(Paragraph {
+                        tag,
+                        ..Default::default()
+                    });
+                    paras.last_mut().unwrap()
+                }
+            };
+            if para.texts.len() <= index {
+                para.texts.resize(index + 1, String::new());
+            }
+            para.texts[index] = text;
+            // The keys are calculated from the texts.
+            para.text_keys = Default::default();
+        }
+        let base_res_key = self.choose_from_keys(&self.base_lang, &self.res).clone();
+        let base_keys = self
+            .res
+            .get(&base_res_key)
+            .map(|res| res.keys().cloned().collect::<Vec<_>>())
+            .unwrap_or_default();
+        for key in base_keys {
+            if let Some(s) = trans.get(format!("#{}", key).as_str()) {
+                self.res
+                    .entry(loc.clone())
+                    .or_default()
+                    .insert(key, RawValue::Str(s.to_string()));
+            }
+        }
+        Ok(())
+    }
+}
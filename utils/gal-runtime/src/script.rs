@@ -4,26 +4,53 @@ use crate::{plugin::Runtime, *};
 use fallback::Fallback;
 use gal_script::*;
 use log::{error, warn};
+use std::collections::HashMap;
 
 /// The variable table in scripts.
 pub struct VarTable<'a> {
     /// The plugin runtime.
     pub runtime: &'a Runtime,
+    /// The game config.
+    pub game: &'a Game,
+    /// The current locale.
+    /// It is used by the plural rules, and the number and date formatting.
+    pub locale: Locale,
     /// The resource map.
     pub res: Fallback<&'a VarMap>,
     /// The context variables.
     pub locals: &'a mut VarMap,
+    /// The current paragraph tag.
+    pub cur_para: &'a str,
+    /// The visit counts of the paragraphs in this playthrough.
+    pub visits: &'a HashMap<String, usize>,
+    /// The count of the texts run before the current one.
+    pub turns: usize,
+    /// The global record, across the playthroughs.
+    pub global_record: &'a GlobalRecord,
     /// The locale variables.
     pub vars: VarMap,
 }
 
 impl<'a> VarTable<'a> {
     /// Creates a new [`VarTable`].
-    pub fn new(runtime: &'a Runtime, res: Fallback<&'a VarMap>, locals: &'a mut VarMap) -> Self {
+    pub fn new(
+        runtime: &'a Runtime,
+        game: &'a Game,
+        locale: Locale,
+        res: Fallback<&'a VarMap>,
+        ctx: &'a mut RawContext,
+        global_record: &'a GlobalRecord,
+    ) -> Self {
         Self {
             runtime,
+            game,
+            locale,
             res,
-            locals,
+            locals: &mut ctx.locals,
+            cur_para: &ctx.cur_para,
+            visits: &ctx.visits,
+            turns: ctx.turns,
+            global_record,
             vars: VarMap::default(),
         }
     }
@@ -160,21 +187,22 @@ fn bin_logic(ctx: &mut VarTable, lhs: &Expr, op: &LogicBinaryOp, rhs: &Expr) ->
     let res = match op {
         LogicBinaryOp::And => lhs.call(ctx).get_bool() && rhs.call(ctx).get_bool(),
         LogicBinaryOp::Or => lhs.call(ctx).get_bool() || rhs.call(ctx).get_bool(),
-        op => {
-            let lhs = lhs.call(ctx);
-            let rhs = rhs.call(ctx);
-            let t = lhs.get_type().max(rhs.get_type());
-            match t {
-                ValueType::Unit => false,
-                ValueType::Bool => bin_ord_logic(&lhs.get_bool(), op, &rhs.get_bool()),
-                ValueType::Num => bin_ord_logic(&lhs.get_num(), op, &rhs.get_num()),
-                ValueType::Str => bin_ord_logic(&lhs.get_str(), op, &rhs.get_str()),
-            }
-        }
+        op => bin_cmp_logic(&lhs.call(ctx), op, &rhs.call(ctx)),
     };
     RawValue::Bool(res)
 }
 
+/// Compares two values, with the comparison operators.
+pub(crate) fn bin_cmp_logic(lhs: &RawValue, op: &LogicBinaryOp, rhs: &RawValue) -> bool {
+    let t = lhs.get_type().max(rhs.get_type());
+    match t {
+        ValueType::Unit => false,
+        ValueType::Bool => bin_ord_logic(&lhs.get_bool(), op, &rhs.get_bool()),
+        ValueType::Num => bin_ord_logic(&lhs.get_num(), op, &rhs.get_num()),
+        ValueType::Str => bin_ord_logic(&lhs.get_str(), op, &rhs.get_str()),
+    }
+}
+
 fn bin_ord_logic<T: Ord>(lhs: &T, op: &LogicBinaryOp, rhs: &T) -> bool {
     match op {
         LogicBinaryOp::Eq => lhs == rhs,
@@ -199,6 +227,99 @@ fn assign(ctx: &mut VarTable, e: &Expr, val: RawValue) -> RawValue {
     RawValue::Unit
 }
 
+/// The intrinsic functions, which are called without namespace.
+pub(crate) const INTRINSICS: &[&str] =
+    &["if", "plural", "number", "date", "visits", "seen", "turns"];
+
+/// Selects a plural form of the number with the current locale.
+///
+/// Without forms, returns the name of the plural category.
+/// Otherwise, the forms are given for the plural categories of the locale,
+/// in the order from `zero` to `other`.
+/// If the form of the category is not provided,
+/// the form of `few` is chosen, and then the last form.
+/// Therefore the forms of `one`, `few` and `many` are enough for the decimals
+/// in languages like Russian and Polish.
+fn plural(ctx: &mut VarTable, args: &[Expr]) -> RawValue {
+    let n = args.first().call(ctx).get_str().into_owned();
+    let cat = ctx.locale.plural_category(&n).unwrap_or_else(|| {
+        warn!("Invalid number `{}` in `plural`.", n);
+        PluralCategory::Other
+    });
+    match args.get(1..) {
+        Some(forms) if !forms.is_empty() => {
+            let cats = ctx.locale.plural_categories();
+            let form = |cat| {
+                cats.iter()
+                    .position(|c| *c == cat)
+                    .and_then(|i| forms.get(i))
+            };
+            form(cat)
+                .or_else(|| form(PluralCategory::Few))
+                .or_else(|| forms.last())
+                .call(ctx)
+        }
+        _ => RawValue::Str(plural_category_name(cat).to_string()),
+    }
+}
+
+/// Formats the number with the separators of the current locale.
+fn number(ctx: &mut VarTable, args: &[Expr]) -> RawValue {
+    let n = args.first().call(ctx).get_str().into_owned();
+    match ctx.locale.format_number(&n) {
+        Some(s) => RawValue::Str(s),
+        None => {
+            warn!("Invalid number `{}` in `number`.", n);
+            RawValue::Str(n)
+        }
+    }
+}
+
+/// Formats the date of the time, in seconds since the UNIX epoch,
+/// with the current locale.
+fn date(ctx: &mut VarTable, args: &[Expr]) -> RawValue {
+    let secs = args.first().call(ctx).get_num();
+    match ctx.locale.format_date(secs) {
+        Some(s) => RawValue::Str(s),
+        None => {
+            warn!("Invalid time `{}` in `date`.", secs);
+            RawValue::Str(secs.to_string())
+        }
+    }
+}
+
+/// Gets the visit count of the paragraph in this playthrough.
+///
+/// Without arguments, returns the visit count of the current paragraph.
+fn visits(ctx: &mut VarTable, args: &[Expr]) -> RawValue {
+    let tag = match args.first() {
+        Some(tag) => tag.call(ctx).into_str(),
+        None => ctx.cur_para.to_string(),
+    };
+    RawValue::Num(ctx.visits.get(&tag).copied().unwrap_or_default() as i64)
+}
+
+/// Determines whether the paragraph, or the text at the index of it,
+/// has been read in any playthrough.
+fn seen(ctx: &mut VarTable, args: &[Expr]) -> RawValue {
+    let tag = args.first().call(ctx).into_str();
+    let index = args.get(1).map(|index| index.call(ctx).get_num());
+    let seen = match index {
+        Some(index) => usize::try_from(index)
+            .ok()
+            .and_then(|index| ctx.game.find_para_base(&tag)?.text_key(index))
+            .map(|key| ctx.global_record.is_read(&tag, key))
+            .unwrap_or_default(),
+        None => ctx
+            .global_record
+            .read
+            .get(&tag)
+            .map(|keys| !keys.is_empty())
+            .unwrap_or_default(),
+    };
+    RawValue::Bool(seen)
+}
+
 fn call(ctx: &mut VarTable, ns: &str, name: &str, args: &[Expr]) -> RawValue {
     if ns.is_empty() {
         match name {
@@ -208,6 +329,12 @@ fn call(ctx: &mut VarTable, ns: &str, name: &str, args: &[Expr]) -> RawValue {
                 args.get(2)
             }
             .call(ctx),
+            "plural" => plural(ctx, args),
+            "number" => number(ctx, args),
+            "date" => date(ctx, args),
+            "visits" => visits(ctx, args),
+            "seen" => seen(ctx, args),
+            "turns" => RawValue::Num(ctx.turns as i64),
             _ => unimplemented!("intrinstics"),
         }
     } else {
@@ -286,8 +413,17 @@ mod test {
                 runtime.await.unwrap()
             })
             .await;
-        let mut locals = VarMap::default();
-        let mut ctx = VarTable::new(runtime, Fallback::new(None, None), &mut locals);
+        let mut raw_ctx = RawContext::default();
+        let game = Game::default();
+        let global_record = GlobalRecord::default();
+        let mut ctx = VarTable::new(
+            runtime,
+            &game,
+            locale!("en"),
+            Fallback::new(None, None),
+            &mut raw_ctx,
+            &global_record,
+        );
         f(&mut ctx);
     }
 
@@ -368,6 +504,33 @@ mod test {
         .await;
     }
 
+    #[tokio::test]
+    async fn plural() {
+        with_ctx(|ctx| {
+            let call = |ctx: &mut VarTable, s: &str| {
+                ProgramParser::new()
+                    .parse(s)
+                    .ok()
+                    .call(ctx)
+                    .get_str()
+                    .into_owned()
+            };
+            assert_eq!(call(ctx, "plural(1)"), "one");
+            assert_eq!(call(ctx, r#"plural(2, "apple", "apples")"#), "apples");
+            assert_eq!(call(ctx, "number(1234567)"), "1,234,567");
+            assert_eq!(call(ctx, "date(1662815100)"), "Sep 10, 2022");
+
+            ctx.locale = locale!("ru");
+            let apples = r#"plural(a, "яблоко", "яблока", "яблок")"#;
+            assert_eq!(call(ctx, &format!("a = 21; {}", apples)), "яблоко");
+            assert_eq!(call(ctx, &format!("a = 3; {}", apples)), "яблока");
+            assert_eq!(call(ctx, &format!("a = 11; {}", apples)), "яблок");
+            assert_eq!(call(ctx, &format!(r#"a = "1.5"; {}"#, apples)), "яблока");
+            assert_eq!(call(ctx, r#"number("1234567.5")"#), "1\u{a0}234\u{a0}567,5");
+        })
+        .await;
+    }
+
     #[tokio::test]
     async fn format() {
         with_ctx(|ctx| {
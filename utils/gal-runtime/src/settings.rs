@@ -1,38 +1,121 @@
 pub use gal_bindings_types::VarMap;
 
-use crate::*;
-use anyhow::{anyhow, Result};
-use dirs::{config_dir, data_local_dir};
-use futures_util::TryStreamExt;
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use crate::{storage::SETTINGS_FILE, *};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     path::{Path, PathBuf},
 };
-use tokio_stream::wrappers::ReadDirStream;
 
 /// The settings of the game.
 #[derive(Debug, Default, Clone, Deserialize, Serialize)]
 pub struct Settings {
-    /// The display language.
-    pub lang: Locale,
+    /// The preferred display languages, the most preferred first.
+    pub lang: LocalePreferences,
+    /// The compaction policy of the history.
+    #[serde(default)]
+    pub history: HistorySettings,
+    /// The auto-save policy.
+    #[serde(default)]
+    pub auto_save: AutoSaveSettings,
 }
 
 impl Settings {
-    /// Creates [`Settings`] object with current locale.
+    /// Creates [`Settings`] object with the preferences of current user.
     pub fn new() -> Self {
         Self {
-            lang: Locale::current(),
+            lang: LocalePreferences::current(),
+            ..Default::default()
         }
     }
 }
 
+/// The compaction policy of the history in [`ActionRecord`].
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct HistorySettings {
+    /// The count of the latest actions kept in full.
+    /// If [`None`], the history is never compacted.
+    /// At least the last action is always kept.
+    pub cap: Option<usize>,
+    /// Before the latest actions, only a snapshot every such count of actions is kept,
+    /// counted by the position in the playthrough.
+    /// If 0, all actions before the latest ones are dropped.
+    #[serde(default)]
+    pub snapshot_interval: usize,
+}
+
+/// The auto-save policy of [`Context`].
+///
+/// The auto save is written to [`SaveSlot::Auto`] when any of the conditions is met.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AutoSaveSettings {
+    /// Save after a switch is chosen.
+    pub on_switch: bool,
+    /// Save when entering a new paragraph.
+    pub on_paragraph: bool,
+    /// Save every such count of actions.
+    /// If 0, it is disabled.
+    pub interval: usize,
+}
+
 /// The global record.
 #[derive(Debug, Default, Clone, Deserialize, Serialize)]
 pub struct GlobalRecord {
+    /// The read texts.
     /// The key is the tag of paragraphs,
-    /// the value is the maximum text index.
+    /// the value is the stable keys of the read texts.
+    #[serde(default)]
+    pub read: HashMap<String, HashSet<String>>,
+    /// The legacy read record, keyed by the tag of paragraphs,
+    /// and the value is the maximum text index.
+    /// It is converted into `read` by [`GlobalRecord::upgrade_read`].
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub record: HashMap<String, usize>,
+    /// The unlocked items, indexed by id.
+    /// The value is the unlock time, in seconds since the UNIX epoch.
+    #[serde(default)]
+    pub unlocks: HashMap<String, u64>,
+    /// The reached endings, indexed by paragraph tag.
+    /// The value is the first reach time, in seconds since the UNIX epoch.
+    #[serde(default)]
+    pub endings: HashMap<String, u64>,
+}
+
+impl GlobalRecord {
+    /// Determines if the text with the stable key in the paragraph has been read.
+    pub fn is_read(&self, tag: &str, key: &str) -> bool {
+        self.read
+            .get(tag)
+            .map(|keys| keys.contains(key))
+            .unwrap_or_default()
+    }
+
+    /// Marks the text with the stable key in the paragraph as read.
+    pub fn mark_read(&mut self, tag: &str, key: &str) {
+        if !self.is_read(tag, key) {
+            self.read
+                .entry(tag.to_string())
+                .or_default()
+                .insert(key.to_string());
+        }
+    }
+
+    /// Converts the legacy index-based `record` into the key-based `read`,
+    /// with the texts of the game.
+    pub fn upgrade_read(&mut self, game: &Game) {
+        for (tag, max_act) in std::mem::take(&mut self.record) {
+            match game.find_para_base(&tag) {
+                Some(para) => {
+                    for key in para.text_keys().iter().take(max_act + 1) {
+                        self.mark_read(&tag, key);
+                    }
+                }
+                None => log::warn!("Cannot find paragraph `{}` in the read record.", tag),
+            }
+        }
+    }
 }
 
 /// The specific record.
@@ -63,97 +146,278 @@ impl ActionRecord {
             ..Default::default()
         })
     }
+
+    /// Compacts the history with the policy.
+    ///
+    /// The snapshots are chosen by the positions of the actions in the playthrough,
+    /// which are saved as `turns` in the contexts,
+    /// so that compacting a compacted record changes nothing.
+    /// Legacy records don't save `turns`, and the indices in the history are used instead.
+    pub fn compact(&mut self, policy: &HistorySettings) {
+        if let Some(cap) = policy.cap {
+            let end = self.history.len().saturating_sub(cap.max(1));
+            let latest = self.history.split_off(end);
+            let mut index = 0;
+            self.history.retain(|act| {
+                let turns = if index > 0 && act.ctx.turns == 0 {
+                    index
+                } else {
+                    act.ctx.turns
+                };
+                index += 1;
+                policy.snapshot_interval > 0 && turns % policy.snapshot_interval == 0
+            });
+            self.history.extend(latest);
+        }
+    }
+}
+
+const SAVE_EXTENSIONS: [&str; 2] = [SaveFormat::Json.extension(), SaveFormat::Binary.extension()];
+
+async fn load_file<T: Versioned>(storage: &dyn Storage, path: &Path) -> Result<T> {
+    let buffer = storage.read(path).await?;
+    from_versioned_slice(&buffer)
 }
 
-async fn load_file<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T> {
-    let buffer = tokio::fs::read(path).await?;
-    Ok(serde_json::from_slice(&buffer)?)
+async fn save_file<T: Versioned>(
+    storage: &dyn Storage,
+    data: &T,
+    path: &Path,
+    format: SaveFormat,
+) -> Result<()> {
+    let buffer = to_versioned_vec(data, format)?;
+    storage.write(path, &buffer).await
 }
 
-async fn save_file<T: Serialize>(data: &T, path: impl AsRef<Path>, pretty: bool) -> Result<()> {
-    let path = path.as_ref();
-    if let Some(parent) = path.parent() {
-        tokio::fs::create_dir_all(parent).await?;
+/// Finds the save file with any supported extension.
+async fn find_save_file(storage: &dyn Storage, path: &Path) -> PathBuf {
+    for ext in SAVE_EXTENSIONS {
+        let p = path.with_extension(ext);
+        if storage.exists(&p).await {
+            return p;
+        }
     }
-    let buffer = if pretty {
-        serde_json::to_vec_pretty(data)
-    } else {
-        serde_json::to_vec(data)
-    }?;
-    tokio::fs::write(path, &buffer).await?;
-    Ok(())
+    path.with_extension(SaveFormat::Json.extension())
 }
 
-fn settings_path(ident: &str) -> Result<PathBuf> {
-    let path = config_dir().ok_or_else(|| anyhow!("Cannot find config path"))?;
-    Ok(path.join(ident).join("settings.json"))
+/// Saves the file with the extension of the format,
+/// and removes the stale one in other formats.
+async fn save_file_as<T: Versioned>(
+    storage: &dyn Storage,
+    data: &T,
+    path: &Path,
+    format: SaveFormat,
+) -> Result<()> {
+    save_file(
+        storage,
+        data,
+        &path.with_extension(format.extension()),
+        format,
+    )
+    .await?;
+    for ext in SAVE_EXTENSIONS {
+        if ext != format.extension() {
+            storage.remove(&path.with_extension(ext)).await?;
+        }
+    }
+    Ok(())
 }
 
 /// Load settings from JSON file.
-pub async fn load_settings(ident: &str) -> Result<Settings> {
-    load_file(settings_path(ident)?).await
+pub async fn load_settings(storage: &dyn Storage) -> Result<Settings> {
+    load_file(storage, Path::new(SETTINGS_FILE)).await
 }
 
 /// Save settings into pretty JSON file.
-pub async fn save_settings(ident: &str, data: &Settings) -> Result<()> {
-    save_file(data, settings_path(ident)?, true).await
+pub async fn save_settings(storage: &dyn Storage, data: &Settings) -> Result<()> {
+    save_file(
+        storage,
+        data,
+        Path::new(SETTINGS_FILE),
+        SaveFormat::PrettyJson,
+    )
+    .await
 }
 
-fn records_path(ident: &str, game: &str) -> Result<PathBuf> {
-    let path = data_local_dir().ok_or_else(|| anyhow!("Cannot find config path"))?;
-    Ok(path.join(ident).join("save").join(game))
+fn records_path(game: &str) -> PathBuf {
+    Path::new("save").join(game)
 }
 
-fn global_record_path(ident: &str, game: &str) -> Result<PathBuf> {
-    Ok(records_path(ident, game)?.join("global.json"))
+fn global_record_path(game: &str) -> PathBuf {
+    records_path(game).join("global")
 }
 
 /// Load [`GlobalRecord`] from the records folder.
-pub async fn load_global_record(ident: &str, game: &str) -> Result<GlobalRecord> {
-    load_file(global_record_path(ident, game)?).await
+pub async fn load_global_record(storage: &dyn Storage, game: &str) -> Result<GlobalRecord> {
+    let path = find_save_file(storage, &global_record_path(game)).await;
+    load_file(storage, &path).await
 }
 
-/// Save [`GlobalRecord`] into the records folder.
-pub async fn save_global_record(ident: &str, game: &str, data: &GlobalRecord) -> Result<()> {
-    save_file(data, global_record_path(ident, game)?, false).await
+/// Save [`GlobalRecord`] into the records folder, with specified format.
+pub async fn save_global_record(
+    storage: &dyn Storage,
+    game: &str,
+    data: &GlobalRecord,
+    format: SaveFormat,
+) -> Result<()> {
+    save_file_as(storage, data, &global_record_path(game), format).await
 }
 
-/// Load all [`ActionRecord`] from the records folder.
-pub async fn load_records(ident: &str, game: &str) -> Result<Vec<ActionRecord>> {
-    let ctx_path = records_path(ident, game)?;
-    let contexts = ReadDirStream::new(tokio::fs::read_dir(ctx_path).await?)
-        .map_err(anyhow::Error::from)
-        .try_filter_map(|entry| async move {
-            let p = entry.path();
-            if p.extension()
+/// Load all numbered [`ActionRecord`] from the records folder, ordered by the numbers.
+/// The format of each file is detected from the content.
+///
+/// A file failed to load is skipped with a warning, and the others are still loaded.
+pub async fn load_records(storage: &dyn Storage, game: &str) -> Result<Vec<ActionRecord>> {
+    let mut paths = storage
+        .list(&records_path(game))
+        .await?
+        .into_iter()
+        .filter_map(|p| {
+            let ext = p
+                .extension()
                 .map(|s| s.to_string_lossy())
-                .unwrap_or_default()
-                == "json"
-                && p.file_stem()
-                    .map(|s| s.to_string_lossy())
-                    .unwrap_or_default()
-                    != "global"
-            {
-                Ok(Some(load_file(&p).await?))
+                .unwrap_or_default();
+            if SAVE_EXTENSIONS.contains(&ext.as_ref()) {
+                p.file_stem()
+                    .and_then(|s| s.to_str())
+                    .and_then(|s| s.parse::<usize>().ok())
+                    .map(|index| (index, p))
             } else {
-                Ok(None)
+                None
             }
         })
-        .try_collect()
-        .await?;
+        .collect::<Vec<_>>();
+    paths.sort_by_key(|(index, _)| *index);
+    let mut contexts = vec![];
+    for (_, p) in paths {
+        match load_file(storage, &p).await {
+            Ok(ctx) => contexts.push(ctx),
+            Err(e) => log::warn!("Cannot load record {}: {}", p.display(), e),
+        }
+    }
     Ok(contexts)
 }
 
-/// Save all [`ActionRecord`] into the records folder.
-pub async fn save_records(ident: &str, game: &str, contexts: &[ActionRecord]) -> Result<()> {
-    let ctx_path = records_path(ident, game)?;
+/// Save all [`ActionRecord`] into the records folder, with specified format.
+pub async fn save_records(
+    storage: &dyn Storage,
+    game: &str,
+    contexts: &[ActionRecord],
+    format: SaveFormat,
+) -> Result<()> {
+    let ctx_path = records_path(game);
     for (i, ctx) in contexts.iter().enumerate() {
-        save_file(
-            ctx,
-            ctx_path.join(i.to_string()).with_extension("json"),
-            false,
-        )
-        .await?;
+        save_file_as(storage, ctx, &ctx_path.join(i.to_string()), format).await?;
     }
     Ok(())
 }
+
+/// The special save slots, apart from the numbered records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SaveSlot {
+    /// The quick save slot.
+    Quick,
+    /// The auto save slot.
+    Auto,
+}
+
+impl SaveSlot {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Quick => "quick",
+            Self::Auto => "auto",
+        }
+    }
+}
+
+fn slot_record_path(game: &str, slot: SaveSlot) -> PathBuf {
+    records_path(game).join(slot.name())
+}
+
+/// Load the [`ActionRecord`] in the slot from the records folder.
+pub async fn load_slot_record(
+    storage: &dyn Storage,
+    game: &str,
+    slot: SaveSlot,
+) -> Result<ActionRecord> {
+    let path = find_save_file(storage, &slot_record_path(game, slot)).await;
+    load_file(storage, &path).await
+}
+
+/// Save the [`ActionRecord`] into the slot in the records folder, with specified format.
+pub async fn save_slot_record(
+    storage: &dyn Storage,
+    game: &str,
+    slot: SaveSlot,
+    data: &ActionRecord,
+    format: SaveFormat,
+) -> Result<()> {
+    save_file_as(storage, data, &slot_record_path(game, slot), format).await
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+
+    fn record(acts: &[usize]) -> ActionRecord {
+        ActionRecord {
+            history: acts
+                .iter()
+                .enumerate()
+                .map(|(turns, &cur_act)| Action {
+                    ctx: RawContext {
+                        cur_act,
+                        turns,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+
+    fn turns(record: &ActionRecord) -> Vec<usize> {
+        record.history.iter().map(|act| act.ctx.turns).collect()
+    }
+
+    #[test]
+    fn compact() {
+        // The text indices restart in a new paragraph.
+        let mut r = record(&[0, 1, 2, 0, 1, 2, 0, 1, 2, 3]);
+        r.compact(&HistorySettings::default());
+        assert_eq!(turns(&r).len(), 10);
+
+        let policy = HistorySettings {
+            cap: Some(3),
+            snapshot_interval: 2,
+        };
+        r.compact(&policy);
+        assert_eq!(turns(&r), [0, 2, 4, 6, 7, 8, 9]);
+        r.compact(&policy);
+        assert_eq!(turns(&r), [0, 2, 4, 6, 7, 8, 9]);
+
+        r.compact(&HistorySettings {
+            cap: Some(0),
+            snapshot_interval: 0,
+        });
+        assert_eq!(turns(&r), [9]);
+    }
+
+    #[test]
+    fn compact_legacy() {
+        // The legacy records don't save the turns.
+        let mut r = record(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        r.history.iter_mut().for_each(|act| act.ctx.turns = 0);
+        r.compact(&HistorySettings {
+            cap: Some(3),
+            snapshot_interval: 2,
+        });
+        assert_eq!(
+            r.history
+                .iter()
+                .map(|act| act.ctx.cur_act)
+                .collect::<Vec<_>>(),
+            [0, 2, 4, 6, 7, 8, 9]
+        );
+    }
+}
@@ -0,0 +1,321 @@
+use crate::*;
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    path::PathBuf,
+    sync::Arc,
+};
+
+/// A position in a source file.
+/// Both the line and column start from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourcePos {
+    /// The source file.
+    pub file: Arc<PathBuf>,
+    /// The line number.
+    pub line: usize,
+    /// The column number, counted in chars.
+    pub column: usize,
+}
+
+impl Display for SourcePos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file.display(), self.line, self.column)
+    }
+}
+
+/// The span of a text in the source file.
+#[derive(Debug, Clone)]
+pub struct TextSpan {
+    file: Arc<PathBuf>,
+    start: (usize, usize),
+    // The start positions of the lines in the text,
+    // if they could be found in the source.
+    lines: Vec<Option<(usize, usize)>>,
+}
+
+impl TextSpan {
+    /// Gets the source position of a byte offset in the text.
+    ///
+    /// The escaped or folded lines could not be mapped exactly,
+    /// and the start of the text is returned instead.
+    pub fn locate(&self, text: &str, offset: usize) -> SourcePos {
+        let pre = &text[..text.floor_char_boundary(offset)];
+        let line_index = pre.matches('\n').count();
+        let column = pre.rsplit('\n').next().unwrap_or_default().chars().count();
+        let (line, column) = match self.lines.get(line_index) {
+            Some(Some((line, start))) => (*line, start + column),
+            _ => self.start,
+        };
+        SourcePos {
+            file: self.file.clone(),
+            line,
+            column: column + 1,
+        }
+    }
+}
+
+/// A YAML source file of paragraphs.
+#[derive(Debug)]
+pub(crate) struct SourceFile {
+    path: Arc<PathBuf>,
+    text: String,
+}
+
+/// The location of a paragraph in a YAML source file, `paras.<loc>[index]`.
+///
+/// The spans of the texts are located on demand,
+/// because they are only needed to report errors.
+#[derive(Debug, Clone)]
+pub(crate) struct ParaSource {
+    file: Arc<SourceFile>,
+    loc: Locale,
+    index: usize,
+}
+
+/// The error message of a successful probe.
+const PROBE: &str = "text span probe";
+
+/// Navigates to `paras.<loc>[index].texts[text]`,
+/// and fails on the text to get its location from the YAML parser.
+struct Probe<'a> {
+    loc: &'a Locale,
+    index: usize,
+    text: usize,
+}
+
+macro_rules! impl_expecting {
+    ($s:literal) => {
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str($s)
+        }
+    };
+}
+
+/// Visits a map, and deserializes the value of the matched key with the seed.
+fn visit_key<'de, A: MapAccess<'de>, S: DeserializeSeed<'de, Value = ()> + Copy>(
+    mut map: A,
+    matches: impl Fn(&str) -> bool,
+    seed: S,
+) -> Result<(), A::Error> {
+    while let Some(key) = map.next_key::<String>()? {
+        if matches(&key) {
+            map.next_value_seed(seed)?;
+        } else {
+            map.next_value::<IgnoredAny>()?;
+        }
+    }
+    Ok(())
+}
+
+/// Visits a sequence, and deserializes the nth element with the seed.
+fn visit_nth<'de, A: SeqAccess<'de>, S: DeserializeSeed<'de, Value = ()>>(
+    mut seq: A,
+    n: usize,
+    seed: S,
+) -> Result<(), A::Error> {
+    for _ in 0..n {
+        if seq.next_element::<IgnoredAny>()?.is_none() {
+            return Ok(());
+        }
+    }
+    seq.next_element_seed(seed)?;
+    while seq.next_element::<IgnoredAny>()?.is_some() {}
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+struct RootSeed<'a>(&'a Probe<'a>);
+
+#[derive(Clone, Copy)]
+struct LocalesSeed<'a>(&'a Probe<'a>);
+
+#[derive(Clone, Copy)]
+struct ParasSeed<'a>(&'a Probe<'a>);
+
+#[derive(Clone, Copy)]
+struct ParaSeed<'a>(&'a Probe<'a>);
+
+#[derive(Clone, Copy)]
+struct TextsSeed<'a>(&'a Probe<'a>);
+
+struct TextSeed;
+
+macro_rules! impl_map_seed {
+    ($t:ident, $expecting:literal, |$this:ident, $map:ident| $body:expr) => {
+        impl<'de, 'a> DeserializeSeed<'de> for $t<'a> {
+            type Value = ();
+
+            fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<(), D::Error> {
+                d.deserialize_map(self)
+            }
+        }
+
+        impl<'de, 'a> Visitor<'de> for $t<'a> {
+            type Value = ();
+
+            impl_expecting!($expecting);
+
+            fn visit_map<A: MapAccess<'de>>(self, $map: A) -> Result<(), A::Error> {
+                let $this = self;
+                $body
+            }
+        }
+    };
+}
+
+macro_rules! impl_seq_seed {
+    ($t:ident, $expecting:literal, |$this:ident| ($n:expr, $seed:expr)) => {
+        impl<'de, 'a> DeserializeSeed<'de> for $t<'a> {
+            type Value = ();
+
+            fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<(), D::Error> {
+                d.deserialize_seq(self)
+            }
+        }
+
+        impl<'de, 'a> Visitor<'de> for $t<'a> {
+            type Value = ();
+
+            impl_expecting!($expecting);
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<(), A::Error> {
+                let $this = self;
+                visit_nth(seq, $n, $seed)
+            }
+        }
+    };
+}
+
+impl_map_seed!(RootSeed, "a game config", |this, map| visit_key(
+    map,
+    |key| key == "paras",
+    LocalesSeed(this.0)
+));
+
+impl_map_seed!(LocalesSeed, "paragraphs by locale", |this, map| visit_key(
+    map,
+    |key| key.parse::<Locale>().ok().as_ref() == Some(this.0.loc),
+    ParasSeed(this.0)
+));
+
+impl_seq_seed!(ParasSeed, "paragraphs", |this| (
+    this.0.index,
+    ParaSeed(this.0)
+));
+
+impl_map_seed!(ParaSeed, "a paragraph", |this, map| visit_key(
+    map,
+    |key| key == "texts",
+    TextsSeed(this.0)
+));
+
+impl_seq_seed!(TextsSeed, "texts", |this| (this.0.text, TextSeed));
+
+impl<'de> DeserializeSeed<'de> for TextSeed {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<(), D::Error> {
+        d.deserialize_str(self)
+    }
+}
+
+impl<'de> Visitor<'de> for TextSeed {
+    type Value = ();
+
+    impl_expecting!("a text");
+
+    fn visit_str<E: de::Error>(self, _: &str) -> Result<(), E> {
+        Err(E::custom(PROBE))
+    }
+}
+
+impl ParaSource {
+    /// Gets the location of the text scalar, as the 1-based line and column.
+    fn probe(&self, index: usize) -> Option<(usize, usize)> {
+        let probe = Probe {
+            loc: &self.loc,
+            index: self.index,
+            text: index,
+        };
+        let err = RootSeed(&probe)
+            .deserialize(serde_yaml::Deserializer::from_str(&self.file.text))
+            .err()?;
+        if !err.to_string().contains(PROBE) {
+            return None;
+        }
+        err.location().map(|loc| (loc.line(), loc.column()))
+    }
+
+    /// Gets the span of the text at `index`.
+    ///
+    /// The lines of a plain or quoted single-line text, and a literal block,
+    /// are mapped exactly, and the others are mapped to the start of the text.
+    pub fn text_span(&self, index: usize, text: &str) -> Option<TextSpan> {
+        let (line, column) = self.probe(index)?;
+        let source = self.file.text.lines().collect::<Vec<_>>();
+        let mark_line = *source.get(line - 1)?;
+        // The byte position of the scalar.
+        let mark = mark_line
+            .char_indices()
+            .nth(column - 1)
+            .map(|(pos, _)| pos)
+            .unwrap_or(mark_line.len());
+        let indicator = mark_line[mark..].chars().next();
+        let quoted = matches!(indicator, Some('\'' | '"')) as usize;
+        // The indentation of a literal block is detected from its first non-empty line.
+        let indent = source
+            .iter()
+            .skip(line)
+            .find(|s| !s.trim().is_empty())
+            .map(|s| s.len() - s.trim_start_matches(' ').len())
+            .unwrap_or_default();
+        let lines = text
+            .split('\n')
+            .enumerate()
+            .map(|(k, value_line)| {
+                if value_line.trim().is_empty() {
+                    return None;
+                }
+                let (i, pos) = match (indicator, k) {
+                    (Some('|'), _) => (line + k, indent),
+                    (_, 0) => (line - 1, mark + quoted),
+                    // The lines of a folded or quoted multi-line text are joined.
+                    _ => return None,
+                };
+                let source_line = source.get(i)?;
+                source_line
+                    .get(pos..)
+                    .filter(|s| s.starts_with(value_line))
+                    .map(|_| (i + 1, source_line[..pos].chars().count()))
+            })
+            .collect();
+        Some(TextSpan {
+            file: self.file.path.clone(),
+            start: (line, column - 1 + quoted),
+            lines,
+        })
+    }
+}
+
+/// Sets the sources of the paragraphs from the YAML source.
+pub(crate) fn set_para_sources(
+    paras: &mut HashMap<Locale, Vec<Paragraph>>,
+    source: String,
+    path: &std::path::Path,
+) {
+    let file = Arc::new(SourceFile {
+        path: Arc::new(path.to_path_buf()),
+        text: source,
+    });
+    for (loc, paras) in paras.iter_mut() {
+        for (index, para) in paras.iter_mut().enumerate() {
+            para.source = Some(ParaSource {
+                file: file.clone(),
+                loc: loc.clone(),
+                index,
+            });
+        }
+    }
+}
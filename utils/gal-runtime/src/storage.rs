@@ -0,0 +1,225 @@
+use anyhow::{anyhow, Result};
+use dirs::{config_dir, data_local_dir};
+use futures_util::{future::BoxFuture, TryStreamExt};
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use tokio_stream::wrappers::ReadDirStream;
+
+/// The storage of the settings and saves.
+///
+/// All paths are relative to the root of the storage.
+pub trait Storage: Send + Sync {
+    /// Reads the whole file.
+    fn read<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<Vec<u8>>>;
+
+    /// Writes the whole file, and creates the parent directories if needed.
+    fn write<'a>(&'a self, path: &'a Path, data: &'a [u8]) -> BoxFuture<'a, Result<()>>;
+
+    /// Removes the file. It succeeds if the file doesn't exist.
+    fn remove<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<()>>;
+
+    /// Determines if the file exists.
+    fn exists<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, bool>;
+
+    /// Lists the files in the directory.
+    fn list<'a>(&'a self, dir: &'a Path) -> BoxFuture<'a, Result<Vec<PathBuf>>>;
+}
+
+impl<S: Storage + ?Sized> Storage for Arc<S> {
+    fn read<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<Vec<u8>>> {
+        (**self).read(path)
+    }
+
+    fn write<'a>(&'a self, path: &'a Path, data: &'a [u8]) -> BoxFuture<'a, Result<()>> {
+        (**self).write(path, data)
+    }
+
+    fn remove<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<()>> {
+        (**self).remove(path)
+    }
+
+    fn exists<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, bool> {
+        (**self).exists(path)
+    }
+
+    fn list<'a>(&'a self, dir: &'a Path) -> BoxFuture<'a, Result<Vec<PathBuf>>> {
+        (**self).list(dir)
+    }
+}
+
+/// The storage rooted at a directory.
+///
+/// It is useful for portable builds, which store the saves next to the game.
+#[derive(Debug, Clone)]
+pub struct DirStorage {
+    root: PathBuf,
+}
+
+impl DirStorage {
+    /// Creates [`DirStorage`] with the root directory.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl Storage for DirStorage {
+    fn read<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<Vec<u8>>> {
+        Box::pin(async move { Ok(tokio::fs::read(self.root.join(path)).await?) })
+    }
+
+    fn write<'a>(&'a self, path: &'a Path, data: &'a [u8]) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let path = self.root.join(path);
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(path, data).await?;
+            Ok(())
+        })
+    }
+
+    fn remove<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            match tokio::fs::remove_file(self.root.join(path)).await {
+                Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            }
+        })
+    }
+
+    fn exists<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, bool> {
+        Box::pin(async move { tokio::fs::metadata(self.root.join(path)).await.is_ok() })
+    }
+
+    fn list<'a>(&'a self, dir: &'a Path) -> BoxFuture<'a, Result<Vec<PathBuf>>> {
+        Box::pin(async move {
+            let paths = ReadDirStream::new(tokio::fs::read_dir(self.root.join(dir)).await?)
+                .map_ok(|entry| dir.join(entry.file_name()))
+                .try_collect()
+                .await?;
+            Ok(paths)
+        })
+    }
+}
+
+/// The storage in the OS specific directories.
+///
+/// The settings file is stored in the config directory,
+/// and the others are stored in the local data directory.
+#[derive(Debug, Default, Clone)]
+pub struct OsStorage {
+    ident: String,
+}
+
+pub(crate) const SETTINGS_FILE: &str = "settings.json";
+
+impl OsStorage {
+    /// Creates [`OsStorage`] with the identifier of the application.
+    pub fn new(ident: impl Into<String>) -> Self {
+        Self {
+            ident: ident.into(),
+        }
+    }
+
+    fn dir(&self, path: &Path) -> Result<DirStorage> {
+        let root = if path == Path::new(SETTINGS_FILE) {
+            config_dir().ok_or_else(|| anyhow!("Cannot find config path"))?
+        } else {
+            data_local_dir().ok_or_else(|| anyhow!("Cannot find data path"))?
+        };
+        Ok(DirStorage::new(root.join(&self.ident)))
+    }
+}
+
+impl Storage for OsStorage {
+    fn read<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<Vec<u8>>> {
+        Box::pin(async move { self.dir(path)?.read(path).await })
+    }
+
+    fn write<'a>(&'a self, path: &'a Path, data: &'a [u8]) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move { self.dir(path)?.write(path, data).await })
+    }
+
+    fn remove<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move { self.dir(path)?.remove(path).await })
+    }
+
+    fn exists<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, bool> {
+        Box::pin(async move {
+            match self.dir(path) {
+                Ok(dir) => dir.exists(path).await,
+                Err(_) => false,
+            }
+        })
+    }
+
+    fn list<'a>(&'a self, dir: &'a Path) -> BoxFuture<'a, Result<Vec<PathBuf>>> {
+        Box::pin(async move { self.dir(dir)?.list(dir).await })
+    }
+}
+
+/// The storage in memory.
+///
+/// Nothing is persisted. It is useful for tests.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    files: Mutex<HashMap<PathBuf, Vec<u8>>>,
+}
+
+impl MemoryStorage {
+    /// Creates an empty [`MemoryStorage`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn read<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<Vec<u8>>> {
+        Box::pin(async move {
+            self.files
+                .lock()
+                .unwrap()
+                .get(path)
+                .cloned()
+                .ok_or_else(|| anyhow!("Cannot find file {}", path.display()))
+        })
+    }
+
+    fn write<'a>(&'a self, path: &'a Path, data: &'a [u8]) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            self.files
+                .lock()
+                .unwrap()
+                .insert(path.to_path_buf(), data.to_vec());
+            Ok(())
+        })
+    }
+
+    fn remove<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            self.files.lock().unwrap().remove(path);
+            Ok(())
+        })
+    }
+
+    fn exists<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, bool> {
+        Box::pin(async move { self.files.lock().unwrap().contains_key(path) })
+    }
+
+    fn list<'a>(&'a self, dir: &'a Path) -> BoxFuture<'a, Result<Vec<PathBuf>>> {
+        Box::pin(async move {
+            Ok(self
+                .files
+                .lock()
+                .unwrap()
+                .keys()
+                .filter(|p| p.parent() == Some(dir))
+                .cloned()
+                .collect())
+        })
+    }
+}
@@ -0,0 +1,20 @@
+#![allow(dead_code)]
+
+use gal_runtime::*;
+use std::path::PathBuf;
+
+pub fn game_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/games")
+        .join(name)
+}
+
+pub async fn open_game(name: &str) -> Game {
+    Game::open(game_path(name)).await.unwrap()
+}
+
+pub async fn open_context(name: &str) -> Context {
+    Context::open(game_path(name), FrontendType::Text)
+        .await
+        .unwrap()
+}
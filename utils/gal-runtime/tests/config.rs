@@ -0,0 +1,138 @@
+mod common;
+
+use common::*;
+use gal_runtime::*;
+
+#[tokio::test]
+async fn include() {
+    let game = Game::open(game_path("include/main.yaml")).await.unwrap();
+    let tags = game.paras[&locale!("en")]
+        .iter()
+        .map(|p| p.tag.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(tags, ["prologue", "chapter1", "chapter2"]);
+    assert_eq!(game.paras[&locale!("ja")].len(), 1);
+    assert_eq!(game.props["bg"], "main.png");
+    assert_eq!(game.props["bgm"], "chapter1.mp3");
+    assert_eq!(game.res[&locale!("ja")]["ch_a"].get_str(), "アリス");
+}
+
+#[tokio::test]
+async fn duplicate() {
+    let err = Game::open(game_path("include/dup.yaml"))
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("`chapter1`"));
+    assert!(err.contains("dup.yaml"));
+    assert!(err.contains("1.yaml"));
+}
+
+#[tokio::test]
+async fn missing() {
+    let err = Game::open(game_path("include/missing.yaml"))
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("chapters/3.yaml"));
+}
+
+#[tokio::test]
+async fn spans() {
+    let game = Game::open(game_path("spans.yaml")).await.unwrap();
+    let para = &game.paras[&locale!("en")][0];
+    let locate = |index: usize, pat: &str| {
+        let text = &para.texts[index];
+        let pos = para
+            .text_span(index)
+            .unwrap()
+            .locate(text, text.find(pat).unwrap());
+        assert!(pos.file.ends_with("spans.yaml"));
+        (pos.line, pos.column)
+    };
+    assert_eq!(locate(0, "Hello"), (10, 11));
+    assert_eq!(locate(1, "\\ch"), (11, 17));
+    assert_eq!(locate(2, "\\ch"), (12, 19));
+    assert_eq!(locate(3, "\\ch"), (15, 16));
+    // The folded and escaped texts are located at the start.
+    assert_eq!(locate(4, "\\ch"), (16, 11));
+    assert_eq!(locate(5, "\\ch"), (19, 12));
+}
+
+#[tokio::test]
+async fn unknown_field() {
+    let err = Game::open(game_path("unknown.yaml"))
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("unknown.yaml:9:"));
+    assert!(err.contains("did you mean `title`?"));
+}
+
+fn game_value(game: &Game) -> serde_json::Value {
+    serde_json::from_str(&ConfigFormat::Json.to_string(game).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn formats() {
+    let yaml = Game::open(game_path("formats/game.yaml")).await.unwrap();
+    let json = Game::open(game_path("formats/game.json")).await.unwrap();
+    let toml = Game::open(game_path("formats/game.toml")).await.unwrap();
+    assert_eq!(game_value(&yaml), game_value(&json));
+    assert_eq!(game_value(&yaml), game_value(&toml));
+    assert_eq!(json.paras[&locale!("en")][0].texts[0], "\\ch{a}Hello.");
+    assert!(json.paras[&locale!("en")][0].text_span(0).is_none());
+}
+
+#[tokio::test]
+async fn convert() {
+    let game = Game::open(game_path("formats/game.yaml")).await.unwrap();
+    let root = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("convert");
+    std::fs::create_dir_all(&root).unwrap();
+    for ext in ["yml", "json", "toml"] {
+        let path = root.join("game").with_extension(ext);
+        game.write(&path).await.unwrap();
+        let converted = Game::open(&path).await.unwrap();
+        assert_eq!(game_value(&game), game_value(&converted));
+    }
+}
+
+#[tokio::test]
+async fn format_error() {
+    let err = Game::open(game_path("formats/bad.toml"))
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("bad.toml:"));
+    assert!(err.contains("did you mean `author`?"));
+    assert!(Game::open(game_path("formats/game.txt")).await.is_err());
+}
+
+#[tokio::test]
+async fn fallback_chain() {
+    let game = Game::open(game_path("fallback.yaml")).await.unwrap();
+    assert_eq!(
+        game.fallback_chain(&locale!("zh-TW"), &game.res),
+        [&locale!("zh-Hant"), &locale!("zh-Hans"), &locale!("en")]
+    );
+    let res = |loc: Locale, key: &str| {
+        game.find_res_fallback(&loc)
+            .and_then(|map| map.get(key))
+            .map(|v| v.get_str().into_owned())
+    };
+    assert_eq!(res(locale!("zh-Hant"), "greeting").unwrap(), "妳好");
+    assert_eq!(res(locale!("zh-Hant"), "farewell").unwrap(), "再见");
+    assert_eq!(res(locale!("ja"), "farewell").unwrap(), "Bye");
+
+    // The explicit fallbacks.
+    assert_eq!(
+        game.fallback_chain(&locale!("ja"), &game.res),
+        [&locale!("zh-Hant"), &locale!("en")]
+    );
+    assert_eq!(
+        game.fallback_chain(&locale!("ja"), &game.paras),
+        [&locale!("ja"), &locale!("zh-Hans"), &locale!("en")]
+    );
+    let para = game.find_para_fallback(&locale!("zh-Hant"), "start");
+    assert_eq!(para.fallback().unwrap().texts[0], "开始。");
+}
@@ -0,0 +1,382 @@
+mod common;
+
+use common::*;
+use gal_runtime::*;
+
+fn action(cur_para: &str, cur_act: usize, act_key: Option<String>) -> Action {
+    Action {
+        ctx: RawContext {
+            cur_para: cur_para.to_string(),
+            cur_act,
+            act_key,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn resolve() {
+    let mut ctx = open_context("keys.yaml").await;
+    let unresolved = ctx.init_context(ActionRecord {
+        history: vec![
+            action("para1", 2, Some("hello".to_string())),
+            action("para1", 1, Some(text_key("World."))),
+            action("para1", 2, Some(text_key("Original line."))),
+            action("para1", 3, None),
+            action("para3", 0, None),
+        ],
+    });
+    let history = &ctx.record.history;
+    assert_eq!(history[0].ctx.cur_act, 0);
+    assert_eq!(history[1].ctx.cur_act, 2);
+    assert_eq!(history[3].ctx.cur_act, 3);
+    assert_eq!(
+        unresolved.iter().map(|u| u.index).collect::<Vec<_>>(),
+        [2, 4]
+    );
+}
+
+#[tokio::test]
+async fn resolve_fallback() {
+    let mut ctx = open_context("fallback.yaml").await;
+    let record = ActionRecord {
+        history: vec![action("other", 0, None)],
+    };
+    // The paragraph only exists in Japanese.
+    ctx.set_locale(locale!("ja"));
+    assert!(ctx.init_context(record.clone()).is_empty());
+    ctx.set_locale(locale!("en"));
+    assert_eq!(ctx.init_context(record).len(), 1);
+}
+
+#[tokio::test]
+async fn key() {
+    let mut ctx = open_context("keys.yaml").await;
+    ctx.init_new();
+    let action = ctx.next_run().unwrap();
+    assert_eq!(action.ctx.act_key.as_deref(), Some("hello"));
+    assert_eq!(action.line[0].as_str(), "Hello.");
+    let action = ctx.next_run().unwrap();
+    assert_eq!(action.ctx.act_key, Some(text_key("Inserted line.")));
+}
+
+#[tokio::test]
+async fn read() {
+    let mut ctx = open_context("keys.yaml").await;
+    let bye = text_key("Bye.");
+    assert_eq!(
+        ctx.game.find_para_base("para2").unwrap().text_keys(),
+        [bye.clone(), format!("{}:1", bye)]
+    );
+
+    // The legacy record is converted into the keys.
+    ctx.set_global_record(GlobalRecord {
+        record: [("para1".to_string(), 1)].into(),
+        ..Default::default()
+    });
+    let global = ctx.global_record();
+    assert!(global.record.is_empty());
+    assert!(global.is_read("para1", "hello"));
+    assert!(global.is_read("para1", &text_key("Inserted line.")));
+    assert!(!global.is_read("para1", &text_key("World.")));
+
+    // The duplicate lines are read separately.
+    ctx.init_new();
+    for _ in 0..5 {
+        ctx.next_run().unwrap();
+    }
+    let action = ctx.next_run().unwrap();
+    assert_eq!(action.ctx.act_key, Some(format!("{}:1", bye)));
+    assert!(ctx.global_record().is_read("para2", &bye));
+    assert!(!ctx.visited(&action));
+}
+
+#[tokio::test]
+async fn negotiate() {
+    let mut ctx = open_context("fallback.yaml").await;
+    ctx.set_locale_preferences(LocalePreferences::parse("fr, zh-TW;q=0.8, ja;q=0.5"));
+    assert_eq!(ctx.locale(), &locale!("zh-Hant"));
+    ctx.set_locale_preferences(LocalePreferences::parse("ja, en"));
+    assert_eq!(ctx.locale(), &locale!("ja"));
+    ctx.set_locale_preferences(LocalePreferences::default());
+    assert_eq!(ctx.locale(), &locale!("en"));
+}
+
+#[tokio::test]
+async fn switch_locale() {
+    let mut ctx = open_context("switch.yaml").await;
+    ctx.set_locale(locale!("en"));
+    ctx.init_new();
+    ctx.next_run().unwrap();
+    let action = ctx.next_run().unwrap();
+    assert_eq!(action.line[0].as_str(), "You have 2 apples.");
+    // Only the changed variables are saved, with the values before them.
+    assert_eq!(
+        ctx.record.history[0].prev_locals,
+        Some([("n".to_string(), RawValue::Unit)].into())
+    );
+    assert_eq!(
+        action.prev_locals,
+        Some([("n".to_string(), RawValue::Num(1))].into())
+    );
+    // The first text is re-rendered with the variables before it.
+    assert_eq!(
+        ctx.rerender(&ctx.record.history[0].clone()).unwrap().line[0].as_str(),
+        "Hello, day 1."
+    );
+
+    let action = ctx.switch_locale(locale!("ja"), false).unwrap();
+    assert_eq!(action.line[0].as_str(), "りんごが2個あります。");
+    assert_eq!(ctx.record.history[0].line[0].as_str(), "Hello, day 1.");
+    assert_eq!(
+        ctx.rerender(&ctx.record.history[0].clone()).unwrap().line[0].as_str(),
+        "こんにちは。"
+    );
+
+    ctx.switch_locale(locale!("ja"), true);
+    assert_eq!(ctx.record.history[0].line[0].as_str(), "こんにちは。");
+    // The position is kept, and the side effects are not applied again.
+    assert_eq!(ctx.ctx.cur_act, 2);
+    assert_eq!(ctx.ctx.locals["n"], RawValue::Num(2));
+    assert_eq!(ctx.record.history[1].ctx.locals["n"], RawValue::Num(2));
+
+    // The variables before the actions are kept after the history is compacted.
+    ctx.record.history.remove(0);
+    ctx.set_locale(locale!("en"));
+    let action = ctx.switch_locale(locale!("en"), true).unwrap();
+    assert_eq!(action.line[0].as_str(), "You have 2 apples.");
+
+    // The actions saved without the variables before them are not re-rendered.
+    let mut legacy = ctx.record.history[0].clone();
+    legacy.prev_locals = None;
+    assert!(ctx.rerender(&legacy).is_none());
+}
+
+#[tokio::test]
+async fn characters() {
+    let mut ctx = open_context("characters.yaml").await;
+    let foo = &ctx.game.characters["foo"];
+    assert_eq!(foo.color.as_deref(), Some("#ff0000"));
+    assert_eq!(
+        foo.voice.as_deref(),
+        Some(std::path::Path::new("voices/foo"))
+    );
+    // The plugins receive the characters as MessagePack arrays.
+    let ch = Character {
+        sprites: [("smile".to_string(), "smile.png".into())].into(),
+        ..foo.clone()
+    };
+    let data = rmp_serde::to_vec(&ch).unwrap();
+    let ch = rmp_serde::from_slice::<Character>(&data).unwrap();
+    assert_eq!(ch.names["ja"], "フー");
+    assert_eq!(ch.model, None);
+    assert_eq!(ch.sprites["smile"], std::path::Path::new("smile.png"));
+
+    ctx.set_locale(locale!("en"));
+    ctx.init_new();
+    let action = ctx.next_run().unwrap();
+    assert_eq!(action.ch_key.as_deref(), Some("foo"));
+    assert_eq!(action.character.as_deref(), Some("A. Foo"));
+    // Not declared, and found in resources.
+    let action = ctx.next_run().unwrap();
+    assert_eq!(action.character.as_deref(), Some("B. Bar"));
+
+    ctx.set_locale(locale!("ja"));
+    ctx.init_new();
+    let action = ctx.next_run().unwrap();
+    assert_eq!(action.character.as_deref(), Some("フー"));
+    // Falls back to the base language.
+    let action = ctx.next_run().unwrap();
+    assert_eq!(action.character.as_deref(), Some("B. Bar"));
+}
+
+#[tokio::test]
+async fn paragraph() {
+    let mut ctx = open_context("paragraph.yaml").await;
+    ctx.init_new();
+    let action = ctx.next_run().unwrap();
+    assert_eq!(action.ctx.locals["enter"], RawValue::Num(1));
+    assert_eq!(action.props["bg"], "room");
+    assert_eq!(action.props["bgm"], "theme");
+
+    let action = ctx.next_run().unwrap();
+    assert_eq!(action.ctx.locals["visits"], RawValue::Num(1));
+    // Going back doesn't enter the paragraph again.
+    ctx.next_back_run();
+    let action = ctx.next_run().unwrap();
+    assert_eq!(action.ctx.locals["enter"], RawValue::Num(1));
+
+    let action = ctx.next_run().unwrap();
+    assert_eq!(action.ctx.cur_para, "end");
+    assert_eq!(action.ctx.locals["exit"], RawValue::Num(1));
+    assert_eq!(action.line[0].as_str(), "2");
+    assert!(action.props.is_empty());
+    assert!(ctx.next_run().is_none());
+}
+
+#[tokio::test]
+async fn visits() {
+    let mut ctx = open_context("visits.yaml").await;
+    ctx.init_new();
+    let action = ctx.next_run().unwrap();
+    assert_eq!(action.line[0].as_str(), "1 0 false");
+    let action = ctx.next_run().unwrap();
+    assert_eq!(action.line[0].as_str(), "2 1 false");
+    let action = ctx.next_run().unwrap();
+    assert_eq!(action.line[0].as_str(), "2 true false");
+    assert_eq!(action.ctx.turns, 2);
+
+    // The counters are rolled back with the context.
+    ctx.next_back_run();
+    ctx.next_back_run();
+    assert_eq!(ctx.ctx.visits["start"], 2);
+    assert!(!ctx.ctx.visits.contains_key("end"));
+    ctx.next_run().unwrap();
+    let action = ctx.next_run().unwrap();
+    assert_eq!(action.ctx.visits["start"], 2);
+    assert_eq!(action.ctx.visits["end"], 1);
+    assert!(ctx.next_run().is_none());
+
+    // The read state is kept across the playthroughs.
+    ctx.init_new();
+    let action = ctx.next_run().unwrap();
+    assert_eq!(action.line[0].as_str(), "1 0 true");
+}
+
+#[tokio::test]
+async fn unlocks() {
+    let mut ctx = open_context("characters.yaml").await;
+    ctx.set_locale(locale!("ja"));
+    ctx.init_new();
+    let action = ctx.next_run().unwrap();
+    assert_eq!(action.line[0].as_str(), "こんにちは。");
+    let time = ctx.global_record().unlocks["cg1"];
+    assert!(time > 0);
+    ctx.next_run().unwrap();
+    assert_eq!(ctx.global_record().unlocks["cg1"], time);
+    assert!(!ctx.unlock("cg1"));
+    assert!(!ctx.unlock("unknown"));
+
+    let unlocks = ctx.unlocks();
+    assert_eq!(unlocks.len(), 2);
+    assert_eq!(unlocks[0].id, "cg1");
+    assert_eq!(unlocks[0].kind, UnlockKind::Cg);
+    assert_eq!(unlocks[0].title, "最初の CG");
+    assert!(unlocks[0].path.as_ref().unwrap().ends_with("cg/cg1.png"));
+    assert_eq!(unlocks[0].unlocked, Some(time));
+    // Falls back to the base language.
+    assert_eq!(unlocks[1].kind, UnlockKind::Achievement);
+    assert_eq!(unlocks[1].title, "Bad end");
+    assert_eq!(unlocks[1].description, "Reach the bad end.");
+    assert_eq!(unlocks[1].unlocked, None);
+
+    assert!(ctx.unlock("bad_end"));
+    assert!(ctx.unlocks()[1].unlocked.is_some());
+}
+
+#[tokio::test]
+async fn endings() {
+    let mut ctx = open_context("endings.yaml").await;
+    let endings = ctx
+        .game
+        .endings()
+        .map(|p| p.tag.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(endings, ["good", "epilogue", "bad"]);
+    assert_eq!(ctx.completion(), 0.0);
+
+    ctx.set_locale(locale!("ja"));
+    ctx.init_new();
+    ctx.ctx.locals.insert("good".into(), RawValue::Bool(true));
+    while ctx.next_run().is_some() {}
+    // Both the marked one and the terminal one are reached.
+    let good = ctx.global_record().endings["good"];
+    assert!(good > 0);
+    assert!(ctx.global_record().endings.contains_key("epilogue"));
+    assert!(!ctx.global_record().endings.contains_key("bad"));
+    assert_eq!(ctx.completion(), 80.0);
+
+    let endings = ctx.endings();
+    assert_eq!(endings[0].title.as_deref(), Some("グッドエンド"));
+    assert_eq!(endings[0].reached, Some(good));
+    assert_eq!(endings[2].title.as_deref(), Some("Bad end"));
+    assert_eq!(endings[2].reached, None);
+
+    ctx.init_new();
+    ctx.ctx.locals.insert("good".into(), RawValue::Bool(false));
+    while ctx.next_run().is_some() {}
+    assert_eq!(ctx.global_record().endings["good"], good);
+    assert!(ctx.endings().iter().all(|e| e.reached.is_some()));
+    assert_eq!(ctx.completion(), 100.0);
+
+    // The paragraph whose next one is missing is not an ending.
+    ctx.init_new();
+    ctx.ctx.locals.insert("lost".into(), RawValue::Bool(true));
+    while ctx.next_run().is_some() {}
+    assert!(!ctx.global_record().endings.contains_key("lost"));
+    assert!(!ctx.global_record().endings.contains_key("nowhere"));
+}
+
+#[tokio::test]
+async fn events() {
+    use std::sync::{Arc, Mutex};
+
+    let mut ctx = open_context("switch.yaml").await;
+    let events = Arc::new(Mutex::new(vec![]));
+    {
+        let events = events.clone();
+        ctx.subscribe(move |e| {
+            events.lock().unwrap().push(match e {
+                ContextEvent::ParagraphChanged { tag, title } => {
+                    format!("para {} {}", tag, title.as_deref().unwrap_or_default())
+                }
+                ContextEvent::VariableChanged { name, value } => {
+                    format!("var {} {:?}", name, value)
+                }
+                ContextEvent::Action { action, visited } => {
+                    format!("action {} {}", action.ctx.cur_act, visited)
+                }
+                ContextEvent::SwitchPresented(switches) => format!("switch {}", switches.len()),
+                ContextEvent::GameEnded(tag) => format!("end {}", tag),
+            })
+        });
+    }
+    let take = || std::mem::take(&mut *events.lock().unwrap());
+
+    ctx.init_new();
+    ctx.next_run().unwrap();
+    assert_eq!(
+        take(),
+        ["para start Start", "var n Some(Num(1))", "action 0 false"]
+    );
+    let action = ctx.next_run().unwrap();
+    assert_eq!(take(), ["var n Some(Num(2))", "action 1 false", "switch 2"]);
+    // The switch action is called by the frontend.
+    ctx.call(&action.switch_actions[0]);
+    assert_eq!(take(), ["var ok Some(Bool(true))"]);
+    ctx.next_run().unwrap();
+    assert_eq!(
+        take(),
+        ["para end ", "var n Some(Num(3))", "action 0 false"]
+    );
+    assert!(ctx.next_run().is_none());
+    assert_eq!(take(), ["end end"]);
+
+    // The context is restored to the snapshot of the popped action.
+    ctx.next_back_run();
+    assert_eq!(take(), ["action 1 true", "switch 2"]);
+    ctx.next_back_run();
+    assert_eq!(
+        take(),
+        ["var n Some(Num(2))", "var ok None", "action 0 true"]
+    );
+
+    // The read actions are visited in a new playthrough.
+    ctx.init_new();
+    ctx.next_run().unwrap();
+    assert_eq!(
+        take(),
+        ["para start Start", "var n Some(Num(1))", "action 0 true"]
+    );
+}
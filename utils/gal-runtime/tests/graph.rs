@@ -0,0 +1,94 @@
+mod common;
+
+use common::*;
+use gal_runtime::*;
+
+fn diag(loc: Locale, tag: &str, kind: DiagnosticKind) -> Diagnostic {
+    Diagnostic::new(&loc, tag, kind)
+}
+
+#[tokio::test]
+async fn graph() {
+    let game = open_game("graph.yaml").await;
+    let graph = StoryGraph::new(&game, &locale!("en"));
+    assert_eq!(graph.start.as_deref(), Some("start"));
+    let start = graph.node("start").unwrap();
+    assert_eq!(start.next.tags.iter().collect::<Vec<_>>(), ["bad", "good"]);
+    assert!(!start.next.end);
+    let reachable = graph.reachable();
+    assert!(reachable.contains("loop2"));
+    assert!(!reachable.contains("orphan"));
+    let ending = graph.ending();
+    assert!(ending.contains("start"));
+    assert!(ending.contains("orphan"));
+    assert!(!ending.contains("good"));
+
+    let graph = StoryGraph::new(&game, &locale!("ja"));
+    assert!(graph.node("extra").is_some());
+    assert!(graph.reachable().contains("extra"));
+}
+
+#[tokio::test]
+async fn analyze() {
+    let game = open_game("graph.yaml").await;
+    let diags = game.analyze();
+    use DiagnosticKind::*;
+    assert_eq!(
+        diags,
+        [
+            diag(locale!("en"), "good", NoEnd),
+            diag(locale!("en"), "bad", MissingTag("missing".into())),
+            diag(locale!("en"), "loop1", NoEnd),
+            diag(locale!("en"), "loop2", NoEnd),
+            diag(locale!("en"), "orphan", Unreachable),
+            diag(locale!("ja"), "bad", MissingInLocale),
+            diag(locale!("ja"), "loop1", MissingInLocale),
+            diag(locale!("ja"), "loop2", MissingInLocale),
+            diag(locale!("ja"), "orphan", MissingInLocale),
+            diag(locale!("ja"), "extra", NotInBase),
+            diag(locale!("ja"), "loop1", Unreachable),
+            diag(locale!("ja"), "loop2", Unreachable),
+        ]
+    );
+    assert_eq!(diags[1].severity(), Severity::Error);
+    assert_eq!(
+        diags[1].to_string(),
+        "error: paragraph `bad` (en): the next paragraph `missing` is missing"
+    );
+}
+
+#[tokio::test]
+async fn export() {
+    let game = open_game("graph.yaml").await;
+    let graphs = game.story_graphs();
+    assert_eq!(graphs.len(), 2);
+    assert_eq!(graphs[0].locale, locale!("en"));
+    let start = graphs[0].node("start").unwrap();
+    assert_eq!(
+        start.edges,
+        [
+            StoryEdge {
+                to: Some("bad".into()),
+                switches: vec!["Bad".into()]
+            },
+            StoryEdge {
+                to: Some("good".into()),
+                switches: vec!["Good".into()]
+            },
+        ]
+    );
+    let orphan = graphs[0].node("orphan").unwrap();
+    assert_eq!(
+        orphan.edges,
+        [StoryEdge {
+            to: None,
+            switches: vec![]
+        }]
+    );
+
+    let dot = graphs[0].to_dot();
+    assert!(dot.starts_with("digraph \"en\" {"));
+    assert!(dot.contains("\"start\" [label=\"The start\", style=bold];"));
+    assert!(dot.contains("\"start\" -> \"good\" [label=\"Good\"];"));
+    assert!(dot.contains("\"orphan\" -> \"[end]\";"));
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use common::*;
+use gal_runtime::*;
+
+#[tokio::test]
+async fn report() {
+    let game = open_game("i18n.yaml").await;
+    let report = game.i18n_report();
+    assert_eq!(report.base, locale!("en"));
+    assert_eq!(
+        report
+            .locales
+            .iter()
+            .map(|r| r.locale.clone())
+            .collect::<Vec<_>>(),
+        [locale!("ja"), locale!("zh")]
+    );
+
+    fn issue(tag: Option<&str>, index: Option<usize>, kind: I18nIssueKind) -> I18nIssue {
+        I18nIssue {
+            tag: tag.map(|s| s.to_string()),
+            index,
+            kind,
+        }
+    }
+
+    use I18nIssueKind::*;
+    let ja = &report.locales[0];
+    assert_eq!(ja.texts, 5);
+    assert_eq!(ja.translated, 3);
+    assert_eq!(
+        ja.issues,
+        [
+            issue(
+                Some("start"),
+                Some(0),
+                Character {
+                    base: vec!["alice".into()],
+                    found: vec!["bob".into()]
+                }
+            ),
+            issue(Some("start"), Some(1), SwitchCount { base: 2, found: 1 }),
+            issue(Some("start"), Some(3), Untranslated),
+            issue(Some("start"), Some(4), ExtraText),
+            issue(Some("end"), None, MissingPara),
+            issue(None, None, MissingRes("ch_bob".into())),
+        ]
+    );
+
+    let zh = &report.locales[1];
+    assert_eq!(zh.translated, 4);
+    assert_eq!(zh.issues, [issue(Some("start"), Some(3), MissingText)]);
+    assert_eq!(zh.completeness(), 80.0);
+}
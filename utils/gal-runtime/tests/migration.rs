@@ -0,0 +1,71 @@
+use gal_runtime::*;
+
+macro_rules! fixture {
+    ($path:literal) => {
+        include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/saves/", $path))
+    };
+}
+
+#[test]
+fn unversioned() {
+    let settings = from_versioned_slice::<Settings>(fixture!("v0/settings.json")).unwrap();
+    assert_eq!(settings.lang, LocalePreferences(vec![locale!("zh-Hans")]));
+
+    let global = from_versioned_slice::<GlobalRecord>(fixture!("v0/global.json")).unwrap();
+    assert_eq!(global.record["walk"], 6);
+
+    let record = from_versioned_slice::<ActionRecord>(fixture!("v0/record.json")).unwrap();
+    assert_eq!(record.history.len(), 2);
+    assert_eq!(record.history[0].switches.len(), 3);
+    assert_eq!(record.last_ctx().unwrap().cur_para, "walk");
+    assert_eq!(
+        record.last_ctx().unwrap().locals["end"],
+        RawValue::Bool(false)
+    );
+}
+
+#[test]
+fn versioned() {
+    let record = from_versioned_slice::<ActionRecord>(fixture!("v1/record.json")).unwrap();
+    assert_eq!(record.history.len(), 1);
+    assert_eq!(record.history[0].ch_key.as_deref(), Some("kdr"));
+}
+
+#[test]
+fn preferences() {
+    let settings = from_versioned_slice::<Settings>(fixture!("v1/settings.json")).unwrap();
+    assert_eq!(settings.lang, LocalePreferences(vec![locale!("ja")]));
+
+    let buffer = to_versioned_vec(&settings, SaveFormat::Json).unwrap();
+    assert!(buffer.starts_with(b"{\"version\":2,\"data\":{\"lang\":[\"ja\"]"));
+}
+
+#[test]
+fn newer() {
+    assert!(from_versioned_slice::<ActionRecord>(fixture!("v99/record.json")).is_err());
+}
+
+#[test]
+fn round_trip() {
+    let record = from_versioned_slice::<ActionRecord>(fixture!("v0/record.json")).unwrap();
+    let buffer = to_versioned_vec(&record, SaveFormat::Json).unwrap();
+    assert!(buffer.starts_with(format!("{{\"version\":{},", ActionRecord::VERSION).as_bytes()));
+    let record = from_versioned_slice::<ActionRecord>(&buffer).unwrap();
+    assert_eq!(record.history.len(), 2);
+}
+
+#[test]
+fn binary() {
+    let record = from_versioned_slice::<ActionRecord>(fixture!("v0/record.json")).unwrap();
+    let json = to_versioned_vec(&record, SaveFormat::Json).unwrap();
+    let buffer = to_versioned_vec(&record, SaveFormat::Binary).unwrap();
+    assert_eq!(SaveFormat::detect(&buffer), SaveFormat::Binary);
+    assert!(buffer.len() < json.len());
+    let record = from_versioned_slice::<ActionRecord>(&buffer).unwrap();
+    assert_eq!(record.history.len(), 2);
+    assert_eq!(record.history[0].switches.len(), 3);
+    assert_eq!(
+        record.last_ctx().unwrap().locals["end"],
+        RawValue::Bool(false)
+    );
+}
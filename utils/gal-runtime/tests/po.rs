@@ -0,0 +1,127 @@
+mod common;
+
+use common::*;
+use gal_runtime::*;
+
+#[tokio::test]
+async fn export() {
+    let game = open_game("po.yaml").await;
+    let catalog = game.export_po(&locale!("ja"));
+    assert_eq!(catalog.locale, Some(locale!("ja")));
+    let entries = catalog
+        .entries
+        .iter()
+        .map(|e| (e.msgctxt.as_str(), e.msgid.as_str(), e.msgstr.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        entries,
+        [
+            (
+                "start/0",
+                "{0}Hello, {1}! 1/2 done.",
+                "{0}こんにちは、{1}！"
+            ),
+            ("start/1", "Choose.", ""),
+            ("start/1/switch/0", "Yes", ""),
+            ("start/1/switch/1", "No", ""),
+            ("start/3", "{0}\"Quoted\".", ""),
+            ("#ch_alice", "Alice", "アリス"),
+            ("#greeting", "Hello,\nworld!", ""),
+        ]
+    );
+    assert_eq!(
+        catalog.entries[0].comments,
+        [r"{0}: \ch{alice}", r"{1}: \exec{#greeting}"]
+    );
+
+    let parsed = PoCatalog::parse(&catalog.to_string()).unwrap();
+    assert_eq!(parsed, catalog);
+}
+
+#[tokio::test]
+async fn import() {
+    let mut game = open_game("po.yaml").await;
+    let mut catalog = game.export_po(&locale!("zh"));
+    let trans = [
+        ("start/0", "{0}你好，{1}！完成1/2。"),
+        ("start/1/switch/0", "是"),
+        ("start/3", "{0}“引用”。"),
+        ("#greeting", "你好，\n世界！"),
+    ];
+    for (ctx, s) in trans {
+        let entry = catalog
+            .entries
+            .iter_mut()
+            .find(|e| e.msgctxt == ctx)
+            .unwrap();
+        entry.msgstr = s.to_string();
+    }
+    catalog.entries[1].msgstr = "Fuzzy".into();
+    catalog.entries[1].fuzzy = true;
+    let catalog = PoCatalog::parse(&catalog.to_string()).unwrap();
+
+    game.import_po(&locale!("zh"), &catalog).unwrap();
+    let para = &game.paras[&locale!("zh")][0];
+    assert_eq!(para.tag, "start");
+    assert_eq!(
+        para.texts,
+        [
+            r"\ch{alice}你好，\exec{#greeting}！完成1\/2。",
+            // Only the switch is translated.
+            "\\switch{是}\\switch{}",
+            "",
+            "\\par “引用”。",
+        ]
+    );
+    assert_eq!(
+        game.res[&locale!("zh")]["greeting"],
+        RawValue::Str("你好，\n世界！".into())
+    );
+    assert!(game.import_po(&locale!("en"), &catalog).is_err());
+
+    // The untranslated text is still to translate.
+    let catalog = game.export_po(&locale!("zh"));
+    let msgstr = |ctx: &str| {
+        let entry = catalog.entries.iter().find(|e| e.msgctxt == ctx).unwrap();
+        entry.msgstr.clone()
+    };
+    assert_eq!(msgstr("start/1"), "");
+    assert_eq!(msgstr("start/1/switch/0"), "是");
+    assert_eq!(msgstr("start/1/switch/1"), "");
+    let report = game.i18n_report();
+    let zh = report
+        .locales
+        .iter()
+        .find(|r| r.locale == locale!("zh"))
+        .unwrap();
+    assert!(zh.issues.contains(&I18nIssue {
+        tag: Some("start".into()),
+        index: Some(1),
+        kind: I18nIssueKind::MissingText,
+    }));
+
+    let part = game.take_locale(&locale!("zh"));
+    assert_eq!(part.paras[&locale!("zh")].len(), 1);
+    assert!(!game.paras.contains_key(&locale!("zh")));
+}
+
+#[tokio::test]
+async fn import_keys() {
+    let mut game = open_game("po.yaml").await;
+    let para = &game.paras[&locale!("ja")][0];
+    assert_eq!(para.text_key(3), None);
+
+    let mut catalog = game.export_po(&locale!("ja"));
+    let entry = catalog
+        .entries
+        .iter_mut()
+        .find(|e| e.msgctxt == "start/3")
+        .unwrap();
+    entry.msgstr = "{0}「引用」。".into();
+    game.import_po(&locale!("ja"), &catalog).unwrap();
+    let para = &game.paras[&locale!("ja")][0];
+    assert_eq!(
+        para.text_key(3),
+        Some(text_key("\\par 「引用」。").as_str())
+    );
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use common::*;
+use gal_runtime::*;
+use std::sync::Arc;
+
+#[tokio::test]
+async fn slots() {
+    let storage = Arc::new(MemoryStorage::new());
+    let mut ctx = open_context("save.yaml").await;
+    ctx.set_storage(storage.clone());
+    let mut settings = Settings::new();
+    settings.auto_save.on_switch = true;
+    settings.auto_save.interval = 2;
+    ctx.set_settings(settings);
+    ctx.init_new();
+
+    let mut saved = vec![];
+    while ctx.next_run().is_some() {
+        saved.push(ctx.auto_save().await.unwrap());
+    }
+    // After the 2nd action, and after the switch.
+    assert_eq!(saved, [false, true, true, false]);
+    let auto = load_slot_record(&*storage, "Save", SaveSlot::Auto)
+        .await
+        .unwrap();
+    assert_eq!(auto.history.len(), 3);
+
+    ctx.save_slot(SaveSlot::Quick).await.unwrap();
+    ctx.init_new();
+    assert!(ctx.record.history.is_empty());
+    assert!(ctx.load_slot(SaveSlot::Quick).await.unwrap().is_empty());
+    assert_eq!(ctx.record.history.len(), 4);
+    assert_eq!(ctx.ctx.cur_para, "para2");
+
+    // The slots are not numbered records.
+    assert!(load_records(&*storage, "Save").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn resume_turns() {
+    let storage = Arc::new(MemoryStorage::new());
+    let mut ctx = open_context("save.yaml").await;
+    ctx.set_storage(storage.clone());
+    ctx.init_new();
+    ctx.next_run().unwrap();
+    ctx.next_run().unwrap();
+    ctx.save_slot(SaveSlot::Quick).await.unwrap();
+
+    let mut ctx = open_context("save.yaml").await;
+    ctx.set_storage(storage);
+    assert!(ctx.load_slot(SaveSlot::Quick).await.unwrap().is_empty());
+    assert_eq!(ctx.ctx.turns, 2);
+    while ctx.next_run().is_some() {}
+    assert_eq!(
+        ctx.record
+            .history
+            .iter()
+            .map(|act| act.ctx.turns)
+            .collect::<Vec<_>>(),
+        (0..ctx.record.history.len()).collect::<Vec<_>>()
+    );
+    assert_eq!(ctx.ctx.turns, ctx.record.history.len());
+}
+
+fn record(cur_para: &str) -> ActionRecord {
+    ActionRecord {
+        history: vec![Action {
+            ctx: RawContext {
+                cur_para: cur_para.to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }],
+    }
+}
+
+async fn records(storage: &dyn Storage) {
+    let records = (0..12).map(|i| record(&i.to_string())).collect::<Vec<_>>();
+    save_records(storage, "Game", &records, SaveFormat::Json)
+        .await
+        .unwrap();
+    save_records(storage, "Game", &records[..1], SaveFormat::Binary)
+        .await
+        .unwrap();
+    save_global_record(storage, "Game", &GlobalRecord::default(), SaveFormat::Json)
+        .await
+        .unwrap();
+    assert!(storage.exists("save/Game/0.bin".as_ref()).await);
+    assert!(!storage.exists("save/Game/0.json".as_ref()).await);
+    // A broken record doesn't prevent the others from loading.
+    storage
+        .write("save/Game/5.json".as_ref(), b"{ broken")
+        .await
+        .unwrap();
+
+    let records = load_records(storage, "Game").await.unwrap();
+    assert_eq!(
+        records
+            .iter()
+            .map(|r| r.last_ctx().unwrap().cur_para.parse::<usize>().unwrap())
+            .collect::<Vec<_>>(),
+        (0..12).filter(|&i| i != 5).collect::<Vec<_>>()
+    );
+    load_global_record(storage, "Game").await.unwrap();
+
+    save_settings(storage, &Settings::new()).await.unwrap();
+    assert!(storage.exists("settings.json".as_ref()).await);
+    load_settings(storage).await.unwrap();
+}
+
+#[tokio::test]
+async fn memory() {
+    records(&MemoryStorage::new()).await;
+}
+
+#[tokio::test]
+async fn dir() {
+    let root = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("storage");
+    if root.exists() {
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+    records(&DirStorage::new(&root)).await;
+    assert!(root.join("save/Game/global.json").exists());
+}
@@ -1,6 +1,7 @@
 //! The script parser.
 
 use crate::*;
+use fallback::FallbackSpec;
 use lalrpop_util::lalrpop_mod;
 use serde::{Deserialize, Serialize};
 
@@ -16,8 +17,8 @@ pub use grammer::{ConstParser, ExprParser, ProgramParser, RefParser};
 /// A full script, a collection of expressions.
 ///
 /// The last expression is the final value of the script.
-#[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq, Eq)]
-pub struct Program(pub Vec<Expr>);
+#[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq, Eq, FallbackSpec)]
+pub struct Program(#[fallback(non_empty)] pub Vec<Expr>);
 
 /// An expression.
 ///
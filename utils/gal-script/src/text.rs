@@ -218,6 +218,16 @@ pub enum Command {
         /// The expression determines whether the switch is enabled.
         enabled: Option<Program>,
     },
+    /// `\id{}`
+    ///
+    /// The stable id of the text.
+    /// It doesn't output anything.
+    Id(String),
+    /// `\unlock{}`
+    ///
+    /// Unlocks an achievement, a CG or a music.
+    /// It doesn't output anything.
+    Unlock(String),
     /// Other custom commands.
     Other(String, Vec<String>),
 }
@@ -395,16 +405,18 @@ impl<'a> TextRichLexer<'a> {
                     '\\' => Ok(RichToken::command(prev_loc, name, vec![])),
                     '{' => {
                         let mut params = vec![];
+                        let mut loc = prev_loc;
                         while let Some(tok) = self.lexer.peek() {
                             if tok.tok == TokenType::SpecChar('{') {
                                 self.lexer.next();
-                                let param = self.parse_param()?;
+                                let (param, end_loc) = self.parse_param()?;
                                 params.push(param);
+                                loc = Loc::from_locs([loc, end_loc]);
                             } else {
                                 break;
                             }
                         }
-                        Ok(RichToken::command(prev_loc, name, params))
+                        Ok(RichToken::command(loc, name, params))
                     }
                     _ => parse_error(loc, ParseErrorType::IllegalChar(c)),
                 },
@@ -415,10 +427,13 @@ impl<'a> TextRichLexer<'a> {
         }
     }
 
-    fn parse_param(&mut self) -> ParseResult<Vec<RichToken<'a>>> {
+    /// Parses a param, and returns the tokens with the location of the end.
+    fn parse_param(&mut self) -> ParseResult<(Vec<RichToken<'a>>, Loc)> {
         self.in_param += 1;
         let mut tokens = vec![];
+        let mut end_loc = Loc(0, 0);
         while let Some(tok) = self.lexer.next() {
+            end_loc = tok.loc;
             match tok.tok {
                 TokenType::Space => tokens.push(RichToken::char(tok.loc, ' ')),
                 TokenType::SpecChar(c) => {
@@ -437,7 +452,7 @@ impl<'a> TextRichLexer<'a> {
                 TokenType::Text(s) => tokens.push(RichToken::text(tok.loc, s)),
             }
         }
-        Ok(tokens)
+        Ok((tokens, end_loc))
     }
 }
 
@@ -475,34 +490,46 @@ impl<'a> TextParser<'a> {
         Ok(Text(self.try_collect()?))
     }
 
-    fn parse_next(&mut self) -> ParseResult<Option<Line>> {
+    /// Parse into [`Line`]s, with the locations in the source.
+    ///
+    /// The location of a [`Line::Cmd`] covers the full command with params.
+    pub fn parse_with_locs(mut self) -> ParseResult<Vec<(Loc, Line)>> {
+        std::iter::from_fn(|| self.parse_next().transpose()).try_collect()
+    }
+
+    fn parse_next(&mut self) -> ParseResult<Option<(Loc, Line)>> {
         let mut str = String::new();
+        let mut locs = vec![];
         while let Some(tok) = self.lexer.peek() {
             match tok {
                 Ok(tok) => match &tok.tok {
                     RichTokenType::Char(c) => {
                         str.push(*c);
+                        locs.push(tok.loc);
                         self.lexer.next();
                     }
                     RichTokenType::Text(s) => {
                         str.push_str(s);
+                        locs.push(tok.loc);
                         self.lexer.next();
                     }
                     RichTokenType::Character(name, alias) => {
                         if str.is_empty() {
+                            let loc = tok.loc;
                             let name = name.to_string();
                             let alias = alias.to_string();
                             self.lexer.next();
-                            return Ok(Some(Line::Cmd(Command::Character(name, alias))));
+                            return Ok(Some((loc, Line::Cmd(Command::Character(name, alias)))));
                         } else {
                             break;
                         }
                     }
                     RichTokenType::Command(name, params) => {
                         if str.is_empty() {
-                            let res = Self::parse_command(tok.loc, name, params)?;
+                            let loc = tok.loc;
+                            let res = Self::parse_command(loc, name, params)?;
                             self.lexer.next();
-                            return Ok(Some(res));
+                            return Ok(Some((loc, res)));
                         } else {
                             break;
                         }
@@ -516,7 +543,10 @@ impl<'a> TextParser<'a> {
         }
         if !str.is_empty() {
             let trimmed_str = SPACE_REGEX.replace_all(&str, " ");
-            Ok(Some(Line::Str(trimmed_str.into_owned())))
+            Ok(Some((
+                Loc::from_locs(locs),
+                Line::Str(trimmed_str.into_owned()),
+            )))
         } else {
             Ok(None)
         }
@@ -617,6 +647,14 @@ impl<'a> TextParser<'a> {
                     enabled,
                 }
             }
+            "id" => {
+                Self::check_params_count(params_count, 1, 1, loc, name)?;
+                Command::Id(Self::concat_params(&params[0])?)
+            }
+            "unlock" => {
+                Self::check_params_count(params_count, 1, 1, loc, name)?;
+                Command::Unlock(Self::concat_params(&params[0])?)
+            }
             name => {
                 let mut args = vec![];
                 for p in params.iter() {
@@ -634,7 +672,7 @@ impl<'a> Iterator for TextParser<'a> {
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.parse_next() {
-            Ok(Some(res)) => Some(Ok(res)),
+            Ok(Some((_, res))) => Some(Ok(res)),
             Ok(None) => None,
             Err(e) => Some(Err(e)),
         }
@@ -750,6 +788,50 @@ mod test_parser {
             .unwrap();
     }
 
+    #[test]
+    fn id() {
+        assert_eq!(
+            TextParser::new(r##"\id{hello}Hello world!"##)
+                .parse()
+                .unwrap(),
+            Text(vec![
+                Line::Cmd(Command::Id("hello".to_string())),
+                Line::Str("Hello world!".to_string())
+            ])
+        );
+    }
+
+    #[test]
+    fn unlock() {
+        assert_eq!(
+            TextParser::new(r##"Hello\unlock{cg1}"##).parse().unwrap(),
+            Text(vec![
+                Line::Str("Hello".to_string()),
+                Line::Cmd(Command::Unlock("cg1".to_string())),
+            ])
+        );
+        assert!(TextParser::new(r##"\unlock{a}{b}"##).parse().is_err());
+    }
+
+    #[test]
+    fn locs() {
+        let text = r##"\ch{a}Hello \ruby{A}{a}\par world/b/B/"##;
+        let res = TextParser::new(text).parse_with_locs().unwrap();
+        assert_eq!(
+            res.iter()
+                .map(|(loc, _)| &text[loc.0..loc.1])
+                .collect::<Vec<_>>(),
+            [
+                r"\ch{a}",
+                "Hello ",
+                r"\ruby{A}{a}",
+                r"\par",
+                "world",
+                "/b/B/"
+            ]
+        );
+    }
+
     #[test]
     fn error() {
         assert_eq!(
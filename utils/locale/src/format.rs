@@ -0,0 +1,77 @@
+use crate::*;
+use fixed_decimal::FixedDecimal;
+use icu_calendar::{DateTime, Iso};
+use icu_datetime::{options::length, DateTimeFormatter};
+use icu_decimal::FixedDecimalFormatter;
+
+impl Locale {
+    /// Formats a decimal number, like `1234567` or `-1234.5`,
+    /// with the decimal and group separators of the locale.
+    ///
+    /// Returns [`None`] if the number is invalid.
+    ///
+    /// ```
+    /// # use locale::locale;
+    /// assert_eq!(locale!("en").format_number("1234567.5").unwrap(), "1,234,567.5");
+    /// assert_eq!(locale!("de").format_number("-1234567.5").unwrap(), "-1.234.567,5");
+    /// assert_eq!(locale!("hi").format_number("1234567").unwrap(), "12,34,567");
+    /// assert_eq!(locale!("es").format_number("1234").unwrap(), "1234");
+    /// ```
+    pub fn format_number(&self, n: &str) -> Option<String> {
+        let n = n.trim().parse::<FixedDecimal>().ok()?;
+        let formatter =
+            FixedDecimalFormatter::try_new(&self.as_ref().into(), Default::default()).ok()?;
+        Some(formatter.format_to_string(&n))
+    }
+
+    /// Formats the date of a time, in seconds since the UNIX epoch,
+    /// with the medium date style of the locale.
+    /// The time is treated as UTC.
+    ///
+    /// Returns [`None`] if the time is out of range.
+    ///
+    /// ```
+    /// # use locale::locale;
+    /// assert_eq!(locale!("en").format_date(1662815100).unwrap(), "Sep 10, 2022");
+    /// assert_eq!(locale!("ja").format_date(1662815100).unwrap(), "2022/09/10");
+    /// ```
+    pub fn format_date(&self, secs: i64) -> Option<String> {
+        let minutes = i32::try_from(secs.div_euclid(60)).ok()?;
+        let date = DateTime::<Iso>::from_minutes_since_local_unix_epoch(minutes).to_any();
+        let options = length::Bag::from_date_style(length::Date::Medium);
+        let formatter = DateTimeFormatter::try_new(&self.as_ref().into(), options.into()).ok()?;
+        formatter.format_to_string(&date).ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::locale;
+
+    #[test]
+    fn number() {
+        let en = locale!("en");
+        assert_eq!(en.format_number("0").unwrap(), "0");
+        assert_eq!(en.format_number("999").unwrap(), "999");
+        assert_eq!(en.format_number("1000").unwrap(), "1,000");
+        assert_eq!(en.format_number("123456").unwrap(), "123,456");
+        assert_eq!(en.format_number("abc"), None);
+        assert_eq!(
+            locale!("fr").format_number("12345.67").unwrap(),
+            "12\u{202f}345,67"
+        );
+        assert_eq!(locale!("pl").format_number("12345").unwrap(), "12\u{a0}345");
+        assert_eq!(
+            locale!("en-IN").format_number("123456789").unwrap(),
+            "12,34,56,789"
+        );
+        assert_eq!(locale!("ja").format_number("1234").unwrap(), "1,234");
+    }
+
+    #[test]
+    fn date() {
+        assert_eq!(locale!("de").format_date(0).unwrap(), "01.01.1970");
+        assert_eq!(locale!("en").format_date(-1).unwrap(), "Dec 31, 1969");
+        assert_eq!(locale!("en").format_date(i64::MAX), None);
+    }
+}
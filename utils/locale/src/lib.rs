@@ -1,12 +1,20 @@
 //! The internal locale lib.
 //!
-//! This crate provides the [`Locale`] type.
+//! This crate provides the [`Locale`] type,
+//! and the [`LocalePreferences`] type to negotiate with a prioritized list.
+//! The plural rules, and the number and date formatting are also provided with CLDR data.
 
 #![warn(missing_docs)]
 #![deny(unsafe_code)]
 #![feature(once_cell)]
 
+mod format;
 mod matcher;
+mod plural;
+mod preferences;
+
+pub use plural::{plural_category_name, PluralCategory};
+pub use preferences::*;
 
 use icu_locid::{LanguageIdentifier, ParserError};
 use matcher::LanguageMatcher;
@@ -19,6 +27,7 @@ static MATCHER: LazyLock<LanguageMatcher> = LazyLock::new(LanguageMatcher::new);
 /// Representation of a language identifier     .
 #[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[repr(transparent)]
+#[serde(transparent)]
 pub struct Locale(pub LanguageIdentifier);
 
 impl Locale {
@@ -61,6 +70,30 @@ impl Locale {
             .matches(self.0.clone(), locales.into_iter().map(|loc| loc.into()))
             .map(|(lang, _)| lang)
     }
+
+    /// Sort the provided locales by the matching distance, the best match first.
+    ///
+    /// The locales which cannot match are excluded.
+    ///
+    /// ```
+    /// # use locale::locale;
+    /// let current = locale!("zh-Hant");
+    /// let accepts = [locale!("ja"), locale!("zh-Hans"), locale!("zh-Hant")];
+    /// assert_eq!(
+    ///     current.sort_from(&accepts),
+    ///     [&locale!("zh-Hant"), &locale!("zh-Hans")],
+    /// );
+    /// ```
+    pub fn sort_from<'a>(
+        &self,
+        locales: impl IntoIterator<Item = impl Into<&'a Locale>>,
+    ) -> Vec<&'a Locale> {
+        MATCHER
+            .sorted_matches(self.0.clone(), locales.into_iter().map(|loc| loc.into()))
+            .into_iter()
+            .map(|(lang, _)| lang)
+            .collect()
+    }
 }
 
 impl Display for Locale {
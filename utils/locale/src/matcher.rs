@@ -1,6 +1,5 @@
-use icu_locid::LanguageIdentifier;
+use icu_locid::{langid, LanguageIdentifier};
 use icu_locid_transform::LocaleExpander;
-use icu_provider_blob::StaticDataProvider;
 use serde::Deserialize;
 use std::collections::{HashMap, HashSet};
 
@@ -143,13 +142,13 @@ const LANGUAGE_INFO: &str = include_str!(concat!(
     env!("CARGO_MANIFEST_DIR"),
     "/data/languageInfo.xml"
 ));
-const CLDR_BIN: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/data/cldr.bin"));
 
 pub struct LanguageMatcher {
     paradiam: HashSet<LanguageIdentifier>,
     vars: Variables,
     rules: Vec<LanguageMatch>,
     expander: LocaleExpander,
+    demotion: u16,
 }
 
 type Variables = HashMap<String, HashSet<String>>;
@@ -179,14 +178,17 @@ impl From<SupplementalData> for LanguageMatcher {
                 }
             }
         }
-        let provider = StaticDataProvider::try_new_from_static_blob(CLDR_BIN).unwrap();
-        let expander = LocaleExpander::try_new_with_buffer_provider(&provider).unwrap();
-        Self {
+        let expander = LocaleExpander::new();
+        let mut matcher = Self {
             paradiam,
             vars,
             rules,
             expander,
-        }
+            demotion: 0,
+        };
+        // Like ICU, the demotion per desired language is the distance between regions.
+        matcher.demotion = matcher.distance(langid!("en-US"), langid!("en-GB"));
+        matcher
     }
 }
 
@@ -198,22 +200,67 @@ impl LanguageMatcher {
 
     pub fn matches<L: AsRef<LanguageIdentifier>>(
         &self,
-        mut desired: LanguageIdentifier,
+        desired: LanguageIdentifier,
         supported: impl IntoIterator<Item = L>,
     ) -> Option<(L, u16)> {
+        self.sorted_matches(desired, supported).into_iter().next()
+    }
+
+    /// The matched languages, sorted by distance.
+    /// The order of the languages with the same distance is kept.
+    pub fn sorted_matches<L: AsRef<LanguageIdentifier>>(
+        &self,
+        mut desired: LanguageIdentifier,
+        supported: impl IntoIterator<Item = L>,
+    ) -> Vec<(L, u16)> {
         self.expander.maximize(&mut desired);
-        supported
+        let mut res = supported
             .into_iter()
             .map(|s| {
                 let mut max_s = s.as_ref().clone();
                 self.expander.maximize(&mut max_s);
                 (s, self.distance_impl(desired.clone(), max_s))
             })
-            .min_by_key(|(_, dis)| *dis)
             .filter(|(_, dis)| *dis < 1000)
+            .collect::<Vec<_>>();
+        res.sort_by_key(|(_, dis)| *dis);
+        res
+    }
+
+    /// Matches a prioritized list of desired languages, the most preferred first.
+    ///
+    /// The distance of each desired language is demoted by its position in the list,
+    /// so that a less preferred language wins only if it matches much better.
+    pub fn matches_list<L: AsRef<LanguageIdentifier>>(
+        &self,
+        desired: impl IntoIterator<Item = LanguageIdentifier>,
+        supported: impl IntoIterator<Item = L>,
+    ) -> Option<(L, u16)> {
+        let supported = supported
+            .into_iter()
+            .map(|s| {
+                let mut max_s = s.as_ref().clone();
+                self.expander.maximize(&mut max_s);
+                (s, max_s)
+            })
+            .collect::<Vec<_>>();
+        let mut best: Option<(usize, u16)> = None;
+        for (i, mut desired) in desired.into_iter().enumerate() {
+            self.expander.maximize(&mut desired);
+            let demotion = self.demotion.saturating_mul(i as u16);
+            for (j, (_, max_s)) in supported.iter().enumerate() {
+                let dis = self.distance_impl(desired.clone(), max_s.clone());
+                if dis < 1000 {
+                    let dis = dis.saturating_add(demotion);
+                    if best.map(|(_, best_dis)| dis < best_dis).unwrap_or(true) {
+                        best = Some((j, dis));
+                    }
+                }
+            }
+        }
+        best.map(|(j, dis)| (supported.into_iter().nth(j).unwrap().0, dis))
     }
 
-    #[allow(unused)]
     pub fn distance(
         &self,
         mut desired: LanguageIdentifier,
@@ -293,4 +340,23 @@ mod test {
         assert_eq!(matcher.distance(langid!("zh-HK"), langid!("zh-MO")), 40);
         assert_eq!(matcher.distance(langid!("zh-HK"), langid!("zh-Hant")), 50);
     }
+
+    #[test]
+    fn matches_list() {
+        let matcher = LanguageMatcher::new();
+        let supported = [langid!("en"), langid!("ja"), langid!("zh-Hant")];
+
+        assert_eq!(
+            matcher.matches_list([langid!("fr"), langid!("ja"), langid!("en")], &supported),
+            Some((&langid!("ja"), matcher.demotion))
+        );
+        // The more preferred language wins if the distances are close.
+        assert_eq!(
+            matcher
+                .matches_list([langid!("zh-HK"), langid!("en")], &supported)
+                .map(|(lang, _)| lang),
+            Some(&langid!("zh-Hant"))
+        );
+        assert_eq!(matcher.matches_list([], &supported), None);
+    }
 }
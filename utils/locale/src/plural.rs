@@ -0,0 +1,91 @@
+use crate::*;
+use fixed_decimal::FixedDecimal;
+use icu_plurals::{PluralRuleType, PluralRules};
+
+pub use icu_plurals::PluralCategory;
+
+/// Gets the CLDR name of the plural category, like `one` or `other`.
+///
+/// ```
+/// # use locale::{plural_category_name, PluralCategory};
+/// assert_eq!(plural_category_name(PluralCategory::Few), "few");
+/// ```
+pub const fn plural_category_name(cat: PluralCategory) -> &'static str {
+    match cat {
+        PluralCategory::Zero => "zero",
+        PluralCategory::One => "one",
+        PluralCategory::Two => "two",
+        PluralCategory::Few => "few",
+        PluralCategory::Many => "many",
+        PluralCategory::Other => "other",
+    }
+}
+
+impl Locale {
+    fn plural_rules(&self) -> Option<PluralRules> {
+        PluralRules::try_new(&self.as_ref().into(), PluralRuleType::Cardinal).ok()
+    }
+
+    /// Get the plural category of a decimal number, like `1` or `-1.50`.
+    ///
+    /// Returns [`None`] if the number is invalid.
+    ///
+    /// ```
+    /// # use locale::{locale, PluralCategory};
+    /// assert_eq!(locale!("en").plural_category("1"), Some(PluralCategory::One));
+    /// assert_eq!(locale!("en").plural_category("1.0"), Some(PluralCategory::Other));
+    /// assert_eq!(locale!("ru").plural_category("22"), Some(PluralCategory::Few));
+    /// assert_eq!(locale!("ja").plural_category("1"), Some(PluralCategory::Other));
+    /// ```
+    pub fn plural_category(&self, n: &str) -> Option<PluralCategory> {
+        let n = n.trim().parse::<FixedDecimal>().ok()?;
+        Some(
+            self.plural_rules()
+                .map(|rules| rules.category_for(&n))
+                .unwrap_or(PluralCategory::Other),
+        )
+    }
+
+    /// Get all plural categories of the locale, in the order from `zero` to `other`.
+    ///
+    /// ```
+    /// # use locale::{locale, PluralCategory::*};
+    /// assert_eq!(locale!("en").plural_categories(), [One, Other]);
+    /// assert_eq!(locale!("pl").plural_categories(), [One, Few, Many, Other]);
+    /// ```
+    pub fn plural_categories(&self) -> Vec<PluralCategory> {
+        match self.plural_rules() {
+            Some(rules) => {
+                let mut cats = rules.categories().collect::<Vec<_>>();
+                cats.sort();
+                cats
+            }
+            None => vec![PluralCategory::Other],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{locale, PluralCategory::*};
+
+    #[test]
+    fn categories() {
+        let cats = |loc: crate::Locale, ns: &[&str]| {
+            ns.iter()
+                .map(|n| loc.plural_category(n).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            cats(locale!("ru"), &["1", "2", "5", "11", "21", "1.5", "-1"]),
+            [One, Few, Many, Many, One, Other, One]
+        );
+        assert_eq!(
+            cats(locale!("ar"), &["0", "1", "2", "3", "11", "100"]),
+            [Zero, One, Two, Few, Many, Other]
+        );
+        assert_eq!(cats(locale!("pt-PT"), &["0"]), [Other]);
+        assert_eq!(cats(locale!("pt-BR"), &["0"]), [One]);
+        assert_eq!(locale!("en").plural_category("one"), None);
+    }
+}
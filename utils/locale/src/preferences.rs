@@ -0,0 +1,137 @@
+use crate::*;
+use std::convert::Infallible;
+
+/// A prioritized list of the desired locales, the most preferred first.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct LocalePreferences(pub Vec<Locale>);
+
+impl LocalePreferences {
+    /// Get the preferences of the current user.
+    ///
+    /// The locales in the `LANGUAGE` environment variable come first,
+    /// followed by [`Locale::current`].
+    pub fn current() -> Self {
+        let mut prefs = std::env::var("LANGUAGE")
+            .map(|s| Self::parse(&s))
+            .unwrap_or_default();
+        prefs.push(Locale::current());
+        prefs
+    }
+
+    /// Parses an `Accept-Language` string like `ja, en-US;q=0.8, en;q=0.5`,
+    /// or a `LANGUAGE` string like `ja_JP.UTF-8:en`.
+    ///
+    /// The locales are sorted by the weights, and the order of the ones with the same weight is kept.
+    /// The invalid ones, the wildcards and the ones with zero weight are skipped.
+    ///
+    /// ```
+    /// # use locale::{locale, LocalePreferences};
+    /// let prefs = LocalePreferences::parse("en;q=0.5, ja, *;q=0.1, zh-CN;q=0.8");
+    /// assert_eq!(prefs.0, [locale!("ja"), locale!("zh-CN"), locale!("en")]);
+    ///
+    /// let prefs = LocalePreferences::parse("ja_JP.UTF-8:en");
+    /// assert_eq!(prefs.0, [locale!("ja-JP"), locale!("en")]);
+    /// ```
+    pub fn parse(s: &str) -> Self {
+        let mut locales = s
+            .split([',', ':'])
+            .filter_map(|item| {
+                let mut parts = item.split(';');
+                let tag = parts.next().unwrap_or_default().trim();
+                let weight = parts
+                    .find_map(|p| p.trim().strip_prefix("q="))
+                    .map(|q| q.trim().parse::<f32>().unwrap_or_default())
+                    .unwrap_or(1.0);
+                // Strip the charset and the modifier of POSIX locales.
+                let tag = tag.split(['.', '@']).next().unwrap_or_default();
+                if weight > 0.0 {
+                    tag.parse::<Locale>().ok().map(|loc| (loc, weight))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        locales.sort_by(|(_, w1), (_, w2)| w2.total_cmp(w1));
+        let mut prefs = Self::default();
+        for (loc, _) in locales {
+            prefs.push(loc);
+        }
+        prefs
+    }
+
+    /// Appends a locale if it isn't in the list.
+    pub fn push(&mut self, loc: Locale) {
+        if !self.0.contains(&loc) {
+            self.0.push(loc);
+        }
+    }
+
+    /// Moves a locale to the front of the list, making it the most preferred one.
+    ///
+    /// ```
+    /// # use locale::{locale, LocalePreferences};
+    /// let mut prefs = LocalePreferences::parse("ja, en");
+    /// prefs.prefer(locale!("en"));
+    /// assert_eq!(prefs.0, [locale!("en"), locale!("ja")]);
+    /// ```
+    pub fn prefer(&mut self, loc: Locale) {
+        self.0.retain(|l| l != &loc);
+        self.0.insert(0, loc);
+    }
+
+    /// The most preferred locale.
+    pub fn first(&self) -> Option<&Locale> {
+        self.0.first()
+    }
+
+    /// Choose the best match from the provided locales, and the matching distance.
+    ///
+    /// A less preferred locale is chosen only if it matches much better.
+    /// Returns [`None`] if it cannot choose a best match.
+    ///
+    /// ```
+    /// # use locale::{locale, LocalePreferences};
+    /// let prefs = LocalePreferences::parse("fr, ja, en");
+    /// let accepts = [locale!("en"), locale!("ja"), locale!("zh-Hans")];
+    /// assert_eq!(
+    ///     prefs.choose_from(&accepts).map(|(loc, _)| loc),
+    ///     Some(&locale!("ja")),
+    /// );
+    /// ```
+    pub fn choose_from<'a>(
+        &self,
+        locales: impl IntoIterator<Item = impl Into<&'a Locale>>,
+    ) -> Option<(&'a Locale, u16)> {
+        MATCHER.matches_list(
+            self.0.iter().map(|loc| loc.0.clone()),
+            locales.into_iter().map(|loc| loc.into()),
+        )
+    }
+}
+
+impl From<Locale> for LocalePreferences {
+    fn from(loc: Locale) -> Self {
+        Self(vec![loc])
+    }
+}
+
+impl Display for LocalePreferences {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, loc) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            loc.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for LocalePreferences {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::parse(s))
+    }
+}